@@ -0,0 +1,150 @@
+//! Typed input events.
+//!
+//! `InputEvent` is `raw::input_event` with its type and code decoded, so that events can be
+//! matched on by meaning rather than by number. Conversion goes both ways without losing anything:
+//! events that don't fit one of the typed variants (unknown codes, out of range values) are kept
+//! as `InputEvent::Unknown`.
+
+use libc::timeval;
+
+use raw::input_event;
+use {Key, RelativeAxis, AbsoluteAxis, Switch, Led, Sound, Misc, FFStatus, Synchronization};
+use {SYNCHRONIZATION, KEY, RELATIVE, ABSOLUTE, MISC, SWITCH, LED, SOUND, FORCEFEEDBACKSTATUS};
+
+/// What happened to a key.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum KeyState {
+    Released = 0,
+    Pressed = 1,
+    /// The key is being held down, and autorepeat kicked in.
+    Repeat = 2,
+}
+
+/// An event read from (or to be written to) a device, with its timestamp.
+///
+/// The axis, switch, LED, sound and misc fields have exactly one flag set.
+#[derive(Copy, Clone, Debug)]
+pub enum InputEvent {
+    Key { time: timeval, key: Key, state: KeyState },
+    Relative { time: timeval, axis: RelativeAxis, value: i32 },
+    Absolute { time: timeval, axis: AbsoluteAxis, value: i32 },
+    Switch { time: timeval, switch: Switch, on: bool },
+    Led { time: timeval, led: Led, on: bool },
+    /// For `SND_TONE`, `value` is the frequency in hertz; for the rest, 1 is on and 0 is off.
+    Sound { time: timeval, sound: Sound, value: i32 },
+    Misc { time: timeval, misc: Misc, value: i32 },
+    Sync { time: timeval, sync: Synchronization },
+    /// The effect with the id `effect` started or stopped playing.
+    FFStatus { time: timeval, effect: i16, status: FFStatus },
+    /// Anything that doesn't fit the variants above.
+    Unknown(input_event),
+}
+
+/// The single flag for `code` in a set of flags with `bits` bits, if there is such a flag.
+fn flag<T>(code: u16, bits: u32, from_bits: fn(u64) -> Option<T>) -> Option<T> {
+    if (code as u32) < bits {
+        from_bits(1 << code)
+    } else {
+        None
+    }
+}
+
+fn bool_value(value: i32) -> Option<bool> {
+    match value {
+        0 => Some(false),
+        1 => Some(true),
+        _ => None,
+    }
+}
+
+impl InputEvent {
+    pub fn time(&self) -> timeval {
+        match *self {
+            InputEvent::Key { time, .. } |
+            InputEvent::Relative { time, .. } |
+            InputEvent::Absolute { time, .. } |
+            InputEvent::Switch { time, .. } |
+            InputEvent::Led { time, .. } |
+            InputEvent::Sound { time, .. } |
+            InputEvent::Misc { time, .. } |
+            InputEvent::Sync { time, .. } |
+            InputEvent::FFStatus { time, .. } => time,
+            InputEvent::Unknown(ev) => ev.time,
+        }
+    }
+
+    fn decode(ev: &input_event) -> Option<InputEvent> {
+        let time = ev.time;
+        let (ty, code, value) = (ev._type, ev.code, ev.value);
+        Some(if ty == SYNCHRONIZATION.number::<u16>() {
+            let sync = match code {
+                0 => Synchronization::SYN_REPORT,
+                1 => Synchronization::SYN_CONFIG,
+                2 => Synchronization::SYN_MT_REPORT,
+                3 => Synchronization::SYN_DROPPED,
+                _ => return None,
+            };
+            if value != 0 {
+                return None;
+            }
+            InputEvent::Sync { time, sync }
+        } else if ty == KEY.number::<u16>() {
+            let state = match value {
+                0 => KeyState::Released,
+                1 => KeyState::Pressed,
+                2 => KeyState::Repeat,
+                _ => return None,
+            };
+            InputEvent::Key { time, key: Key::from_code(code)?, state }
+        } else if ty == RELATIVE.number::<u16>() {
+            let axis = flag(code, 32, |bits| RelativeAxis::from_bits(bits as u32))?;
+            InputEvent::Relative { time, axis, value }
+        } else if ty == ABSOLUTE.number::<u16>() {
+            let axis = flag(code, 64, AbsoluteAxis::from_bits)?;
+            InputEvent::Absolute { time, axis, value }
+        } else if ty == SWITCH.number::<u16>() {
+            let switch = flag(code, 32, |bits| Switch::from_bits(bits as u32))?;
+            InputEvent::Switch { time, switch, on: bool_value(value)? }
+        } else if ty == LED.number::<u16>() {
+            let led = flag(code, 32, |bits| Led::from_bits(bits as u32))?;
+            InputEvent::Led { time, led, on: bool_value(value)? }
+        } else if ty == SOUND.number::<u16>() {
+            let sound = flag(code, 32, |bits| Sound::from_bits(bits as u32))?;
+            InputEvent::Sound { time, sound, value }
+        } else if ty == MISC.number::<u16>() {
+            let misc = flag(code, 32, |bits| Misc::from_bits(bits as u32))?;
+            InputEvent::Misc { time, misc, value }
+        } else if ty == FORCEFEEDBACKSTATUS.number::<u16>() {
+            let status = if (value as u32) < 32 { FFStatus::from_bits(1 << value)? } else { return None };
+            InputEvent::FFStatus { time, effect: code as i16, status }
+        } else {
+            return None;
+        })
+    }
+}
+
+impl From<input_event> for InputEvent {
+    fn from(ev: input_event) -> InputEvent {
+        InputEvent::decode(&ev).unwrap_or(InputEvent::Unknown(ev))
+    }
+}
+
+impl From<InputEvent> for input_event {
+    fn from(ev: InputEvent) -> input_event {
+        let (ty, code, value) = match ev {
+            InputEvent::Key { key, state, .. } => (KEY, key as u16, state as i32),
+            InputEvent::Relative { axis, value, .. } => (RELATIVE, axis.number(), value),
+            InputEvent::Absolute { axis, value, .. } => (ABSOLUTE, axis.number(), value),
+            InputEvent::Switch { switch, on, .. } => (SWITCH, switch.number(), on as i32),
+            InputEvent::Led { led, on, .. } => (LED, led.number(), on as i32),
+            InputEvent::Sound { sound, value, .. } => (SOUND, sound.number(), value),
+            InputEvent::Misc { misc, value, .. } => (MISC, misc.number(), value),
+            InputEvent::Sync { sync, .. } => (SYNCHRONIZATION, sync as u16, 0),
+            InputEvent::FFStatus { effect, status, .. } => {
+                (FORCEFEEDBACKSTATUS, effect as u16, status.number())
+            }
+            InputEvent::Unknown(ev) => return ev,
+        };
+        input_event { time: ev.time(), _type: ty.number(), code, value }
+    }
+}
@@ -0,0 +1,290 @@
+//! Force feedback effects.
+//!
+//! Effects are uploaded to the device with `Device::upload_ff_effect`, which returns a handle that
+//! is used to start and stop the effect. The device only has room for a limited number of effects,
+//! and an effect keeps its slot until its handle is dropped.
+//!
+//! See https://www.kernel.org/doc/Documentation/input/ff.txt for what the parameters mean. Levels
+//! and magnitudes are signed 16-bit fractions of the maximum force the device can produce, and
+//! lengths and intervals are in milliseconds.
+
+use std::os::unix::io::*;
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+use libc;
+use nix::{Errno, Error};
+
+use raw::*;
+use {DeviceError, FFEffect};
+use FFEffect::*;
+use FORCEFEEDBACK;
+
+/// The most samples a custom waveform may have.
+///
+/// The kernel doesn't bound the length itself: `custom_len` is a `__u32` in `struct
+/// ff_periodic_effect` (include/uapi/linux/input.h), and `input_ff_upload` in
+/// drivers/input/ff-core.c hands it to the driver unchecked, leaving each driver to reject
+/// lengths it can't take with an unexplained `EINVAL`. 64Ki samples (128KiB) is far more detail
+/// than a force feedback motor can reproduce in one period, so longer waveforms are refused before
+/// they reach the driver.
+pub const FF_CUSTOM_MAX_SAMPLES: usize = 0x1_0000;
+
+/// The shape of a periodic effect.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sine,
+    SawUp,
+    SawDown,
+    /// An arbitrary shape, given as samples spread evenly over one period. Only a few devices
+    /// support this; check for `FF_CUSTOM` in `Device::ff_effects_supported`. There must be at
+    /// least one sample, and no more than `FF_CUSTOM_MAX_SAMPLES`.
+    Custom(Vec<i16>),
+}
+
+impl Waveform {
+    fn code(&self) -> FFEffect {
+        match *self {
+            Waveform::Square => FF_SQUARE,
+            Waveform::Triangle => FF_TRIANGLE,
+            Waveform::Sine => FF_SINE,
+            Waveform::SawUp => FF_SAW_UP,
+            Waveform::SawDown => FF_SAW_DOWN,
+            Waveform::Custom(_) => FF_CUSTOM,
+        }
+    }
+}
+
+/// The kind of a force feedback effect, along with its kind-specific parameters.
+///
+/// The condition effects take one `ff_condition_effect` per axis, X first.
+#[derive(Clone, Debug)]
+pub enum ForceFeedbackEffect {
+    /// Vibration, as found in most gamepads. Most devices have a strong (low frequency) and a weak
+    /// (high frequency) motor.
+    Rumble { strong_magnitude: u16, weak_magnitude: u16 },
+    /// A force that repeats in a `waveform` pattern every `period` milliseconds.
+    Periodic {
+        waveform: Waveform,
+        period: u16,
+        magnitude: i16,
+        offset: i16,
+        phase: u16,
+        envelope: ff_envelope,
+    },
+    /// A force of a fixed `level`.
+    Constant { level: i16, envelope: ff_envelope },
+    /// A force that changes linearly from `start_level` to `end_level`.
+    Ramp { start_level: i16, end_level: i16, envelope: ff_envelope },
+    /// A force that pulls back towards the center position.
+    Spring([ff_condition_effect; 2]),
+    /// A force that resists movement.
+    Friction([ff_condition_effect; 2]),
+    /// A force that resists movement in proportion to its velocity.
+    Damper([ff_condition_effect; 2]),
+    /// A force that resists changes in velocity.
+    Inertia([ff_condition_effect; 2]),
+}
+
+/// Everything needed to upload an effect: its kind, plus when and in which direction it plays.
+#[derive(Clone, Debug)]
+pub struct FFEffectData {
+    /// Direction the force comes from: 0x0000 is down, 0x4000 left, 0x8000 up and 0xc000 right.
+    pub direction: u16,
+    /// The button that starts the effect, and how long until it can be triggered again.
+    pub trigger: ff_trigger,
+    /// How long the effect plays for (0 means forever) and how long it waits before starting.
+    pub replay: ff_replay,
+    pub kind: ForceFeedbackEffect,
+}
+
+impl FFEffectData {
+    /// An effect of the given kind, which comes from below, isn't bound to a button, and plays
+    /// immediately and forever.
+    pub fn new(kind: ForceFeedbackEffect) -> FFEffectData {
+        FFEffectData {
+            direction: 0,
+            trigger: ff_trigger::default(),
+            replay: ff_replay::default(),
+            kind,
+        }
+    }
+
+    /// The kernel's representation of the effect. For custom waveforms, this points into `self`,
+    /// so it must not outlive the borrow.
+    fn to_raw(&self, id: i16) -> ff_effect {
+        let ty = match self.kind {
+            ForceFeedbackEffect::Rumble { .. } => FF_RUMBLE,
+            ForceFeedbackEffect::Periodic { .. } => FF_PERIODIC,
+            ForceFeedbackEffect::Constant { .. } => FF_CONSTANT,
+            ForceFeedbackEffect::Ramp { .. } => FF_RAMP,
+            ForceFeedbackEffect::Spring(_) => FF_SPRING,
+            ForceFeedbackEffect::Friction(_) => FF_FRICTION,
+            ForceFeedbackEffect::Damper(_) => FF_DAMPER,
+            ForceFeedbackEffect::Inertia(_) => FF_INERTIA,
+        };
+        let mut effect = ff_effect {
+            _type: ty as u16,
+            id,
+            direction: self.direction,
+            trigger: self.trigger,
+            replay: self.replay,
+            u: Union_Unnamed16::default(),
+        };
+        // The union is zeroed and large enough for any of its members.
+        unsafe {
+            match self.kind {
+                ForceFeedbackEffect::Rumble { strong_magnitude, weak_magnitude } => {
+                    *effect.u.rumble() = ff_rumble_effect { strong_magnitude, weak_magnitude };
+                }
+                ForceFeedbackEffect::Periodic { ref waveform, period, magnitude, offset, phase, envelope } => {
+                    let mut periodic = ff_periodic_effect {
+                        waveform: waveform.code() as u16,
+                        period,
+                        magnitude,
+                        offset,
+                        phase,
+                        envelope,
+                        ..ff_periodic_effect::default()
+                    };
+                    if let Waveform::Custom(ref samples) = *waveform {
+                        // The kernel only reads the samples, and copies them during EVIOCSFF.
+                        periodic.custom_len = samples.len() as u32;
+                        periodic.custom_data = samples.as_ptr() as *mut i16;
+                    }
+                    *effect.u.periodic() = periodic;
+                }
+                ForceFeedbackEffect::Constant { level, envelope } => {
+                    *effect.u.constant() = ff_constant_effect { level, envelope };
+                }
+                ForceFeedbackEffect::Ramp { start_level, end_level, envelope } => {
+                    *effect.u.ramp() = ff_ramp_effect { start_level, end_level, envelope };
+                }
+                ForceFeedbackEffect::Spring(conditions) |
+                ForceFeedbackEffect::Friction(conditions) |
+                ForceFeedbackEffect::Damper(conditions) |
+                ForceFeedbackEffect::Inertia(conditions) => {
+                    *effect.u.condition() = conditions;
+                }
+            }
+        }
+        effect
+    }
+}
+
+/// Uploads `data` to the device behind `fd`, as a new effect if `id` is -1 and replacing the
+/// effect `id` otherwise. Returns the id the kernel assigned.
+///
+/// Custom waveforms without samples fail with `EINVAL`, and those with more than
+/// `FF_CUSTOM_MAX_SAMPLES` with `CustomWaveformTooLong`, before reaching the driver.
+fn upload(fd: RawFd, data: &FFEffectData, id: i16) -> Result<i16, DeviceError> {
+    if let ForceFeedbackEffect::Periodic { waveform: Waveform::Custom(ref samples), .. } = data.kind {
+        if samples.is_empty() {
+            return Err(Error::Sys(Errno::EINVAL).into());
+        }
+        if samples.len() > FF_CUSTOM_MAX_SAMPLES {
+            return Err(DeviceError::CustomWaveformTooLong { max: FF_CUSTOM_MAX_SAMPLES });
+        }
+    }
+    let mut effect = data.to_raw(id);
+    // The kernel writes the new id back into the struct.
+    do_ioctl!(eviocsff(fd, &mut effect as *mut ff_effect as *const ff_effect));
+    Ok(effect.id)
+}
+
+/// An effect that has been uploaded to a device. The effect is erased from the device when this
+/// is dropped.
+///
+/// The handle shares the descriptor of the `Device` it was uploaded through, which stays open
+/// until both have been dropped, so it can outlive the `Device`.
+#[derive(Debug)]
+pub struct FFEffectHandle {
+    dev: Arc<::DeviceFd>,
+    id: i16,
+}
+
+impl FFEffectHandle {
+    /// A handle for the effect `id`, uploaded through `dev`.
+    pub(crate) fn new(dev: &::Device, id: i16) -> FFEffectHandle {
+        dev.shared_fd.ff_effects_in_use.fetch_add(1, Ordering::SeqCst);
+        FFEffectHandle { dev: dev.shared_fd.clone(), id }
+    }
+
+    /// The id the kernel assigned to the effect.
+    pub fn id(&self) -> i16 {
+        self.id
+    }
+
+    /// Starts playing the effect, repeating it `count` times.
+    pub fn play(&mut self, count: i32) -> Result<(), Error> {
+        ::write_frame(self.dev.fd, &[input_event {
+            _type: FORCEFEEDBACK.number(),
+            code: self.id as u16,
+            value: count,
+            ..input_event::default()
+        }])
+    }
+
+    pub fn stop(&mut self) -> Result<(), Error> {
+        self.play(0)
+    }
+
+    /// Replaces the effect's parameters. If the effect is playing, it continues with the new
+    /// parameters. Most drivers cannot change the kind of an effect this way.
+    pub fn update(&mut self, data: &FFEffectData) -> Result<(), DeviceError> {
+        upload(self.dev.fd, data, self.id).map(|_| ())
+    }
+}
+
+impl Drop for FFEffectHandle {
+    fn drop(&mut self) {
+        // Nothing to do if erasing fails; the kernel frees the effect when the device is closed
+        // in any case.
+        unsafe {
+            let _ = eviocrmff(self.dev.fd, self.id as libc::c_int);
+        }
+        self.dev.ff_effects_in_use.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl ::Device {
+    /// Uploads a force feedback effect to the device. It doesn't play until `play` is called on
+    /// the returned handle (or its trigger button is pressed).
+    ///
+    /// Fails with `UnsupportedOperation` if the device doesn't do force feedback at all or doesn't
+    /// support custom waveforms and one was given, and with `TooManyFFEffects` if all of the
+    /// device's effect slots are taken.
+    pub fn upload_ff_effect(&mut self, data: &FFEffectData) -> Result<FFEffectHandle, DeviceError> {
+        if !self.ty.contains(FORCEFEEDBACK) {
+            return Err(Error::UnsupportedOperation.into());
+        }
+        if let ForceFeedbackEffect::Periodic { waveform: Waveform::Custom(_), .. } = data.kind {
+            if !self.ff.contains(FF_CUSTOM as usize) {
+                return Err(Error::UnsupportedOperation.into());
+            }
+        }
+        let max = self.ff_effects_max;
+        if self.ff_effects_in_use() >= max {
+            return Err(DeviceError::TooManyFFEffects { max });
+        }
+        match upload(self.fd, data, -1) {
+            Ok(id) => {
+                // Whatever was reported for the effect's id belonged to an earlier effect.
+                self.ff_effect_status.remove(&id);
+                Ok(FFEffectHandle::new(self, id))
+            }
+            Err(e) => {
+                // The slots are shared with everyone else who has the device open, so we can run
+                // out even if our own count says otherwise.
+                if e == DeviceError::Nix(Error::Sys(Errno::ENOSPC)) {
+                    Err(DeviceError::TooManyFFEffects { max })
+                } else {
+                    Err(e)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Keymaps in the format of udev's hardware database.
+//!
+//! udev applies the `KEYBOARD_KEY_<scancode>=<key>` properties it finds for a device every time the
+//! device shows up, which makes a fix found with `Keymap::set` permanent. `Device::keymap_hwdb`
+//! writes out a device's whole keymap in that format, ready to be trimmed down to the keys that
+//! matter and dropped into `/etc/udev/hwdb.d/`. Going the other way, `Device::apply_hwdb` applies
+//! the entries of a hwdb file that match the device.
+//!
+//! ```no_run
+//! use std::fs;
+//! use evdev::hwdb::Hwdb;
+//!
+//! let mut dev = evdev::Device::open(&"/dev/input/event0").unwrap();
+//! let hwdb = Hwdb::parse(&fs::read_to_string("70-keyboard.hwdb").unwrap()).unwrap();
+//! let applied = dev.apply_hwdb(&hwdb).unwrap();
+//! println!("{} keys remapped", applied);
+//! ```
+//!
+//! Only `evdev:input:` match patterns are understood, since those are the only ones that can be
+//! checked against a device's `input_id`. Blocks matching on the device name, DMI data or anything
+//! else are skipped, as are properties other than `KEYBOARD_KEY_`.
+
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write;
+
+use raw::input_id;
+use keymap::Scancode;
+use {Device, Key};
+
+/// One block of a hwdb file: the patterns that select devices, and the keys remapped on them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HwdbEntry {
+    /// The match lines, e.g. `evdev:input:b0003v046Dp*`.
+    pub patterns: Vec<String>,
+    pub keys: Vec<(Scancode, Key)>,
+}
+
+impl HwdbEntry {
+    /// Whether any of the `evdev:input:` patterns matches a device with the given id.
+    ///
+    /// The patterns are matched against `input:b<bus>v<vendor>p<product>e<version>`, with each
+    /// number as four uppercase hex digits. udev matches against the full modalias, which goes on
+    /// to list the device's capabilities; patterns need a trailing `*` to match that either way.
+    pub fn matches(&self, id: &input_id) -> bool {
+        let modalias = modalias(id);
+        self.patterns.iter().any(|pattern| match pattern.strip_prefix("evdev:") {
+            Some(pattern) if pattern.starts_with("input:") => {
+                glob_matches(pattern.as_bytes(), modalias.as_bytes())
+            }
+            _ => false,
+        })
+    }
+}
+
+/// The contents of a hwdb file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Hwdb {
+    pub entries: Vec<HwdbEntry>,
+}
+
+/// Why a hwdb file couldn't be parsed. `line` counts from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HwdbError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for HwdbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl Error for HwdbError {}
+
+impl Hwdb {
+    /// Parses the text of a hwdb file. Blocks are separated by blank lines, and consist of one or
+    /// more match lines followed by property lines, which are indented by a space.
+    pub fn parse(text: &str) -> Result<Hwdb, HwdbError> {
+        let mut hwdb = Hwdb::default();
+        // Whether the current block has had any properties, so that a match line starts a new one.
+        let mut in_props = false;
+        // Whether a blank line ended the current block.
+        let mut ended = true;
+        for (idx, line) in text.lines().enumerate() {
+            let err = |msg: String| HwdbError { line: idx + 1, msg };
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            if line.trim().is_empty() {
+                ended = true;
+                continue;
+            }
+            if !line.starts_with(' ') {
+                if ended || in_props {
+                    hwdb.entries.push(HwdbEntry { patterns: Vec::new(), keys: Vec::new() });
+                    ended = false;
+                    in_props = false;
+                }
+                hwdb.entries.last_mut().unwrap().patterns.push(line.trim_end().to_string());
+                continue;
+            }
+            if ended {
+                return Err(err("property without a match line".to_string()));
+            }
+            in_props = true;
+            let entry = hwdb.entries.last_mut().unwrap();
+            let prop = line.trim();
+            let eq = match prop.find('=') {
+                Some(eq) => eq,
+                None => return Err(err(format!("expected NAME=value, found `{}`", prop))),
+            };
+            let (name, value) = (&prop[..eq], &prop[eq + 1..]);
+            let scancode = match name.strip_prefix("KEYBOARD_KEY_") {
+                Some(scancode) => scancode,
+                None => continue,
+            };
+            let scancode = match u32::from_str_radix(scancode, 16) {
+                Ok(scancode) => scancode,
+                Err(_) => return Err(err(format!("invalid scancode in `{}`", name))),
+            };
+            let key = match key_from_name(value) {
+                Some(key) => key,
+                None => return Err(err(format!("unknown key `{}`", value))),
+            };
+            entry.keys.push((Scancode::from(scancode), key));
+        }
+        Ok(hwdb)
+    }
+}
+
+/// The device part of the modalias, as matched by `evdev:input:` patterns.
+fn modalias(id: &input_id) -> String {
+    format!("input:b{:04X}v{:04X}p{:04X}e{:04X}", id.bustype, id.vendor, id.product, id.version)
+}
+
+/// fnmatch(3)-style matching of `*` and `?`, which is all hwdb patterns use in practice.
+fn glob_matches(pattern: &[u8], s: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => s.is_empty(),
+        Some((&b'*', rest)) => (0..=s.len()).any(|skip| glob_matches(rest, &s[skip..])),
+        Some((&b'?', rest)) => !s.is_empty() && glob_matches(rest, &s[1..]),
+        Some((c, rest)) => s.first() == Some(c) && glob_matches(rest, &s[1..]),
+    }
+}
+
+/// The name udev uses for a key: lowercase, without the `KEY_` prefix but keeping `BTN_`.
+fn key_name(key: Key) -> String {
+    let name = key.name().to_lowercase();
+    match name.strip_prefix("key_") {
+        Some(name) => name.to_string(),
+        None => name,
+    }
+}
+
+fn key_from_name(name: &str) -> Option<Key> {
+    let name = name.to_uppercase();
+    if name.starts_with("BTN_") {
+        name.parse().ok()
+    } else {
+        format!("KEY_{}", name).parse().ok()
+    }
+}
+
+impl Device {
+    /// The device's whole keymap as a hwdb block matching its bus, vendor, product and version.
+    ///
+    /// Unmapped scancodes are included as `reserved`. Mappings that can't be expressed in hwdb
+    /// syntax, to keys without a name or from scancodes longer than 4 bytes, are left out.
+    pub fn keymap_hwdb(&mut self) -> Result<String, ::nix::Error> {
+        let mut out = format!("evdev:{}*\n", modalias(&self.id));
+        for entry in self.keymap().iter() {
+            let entry = entry?;
+            if let (Some(scancode), Some(key)) = (entry.scancode.to_u32(), entry.key()) {
+                writeln!(out, " KEYBOARD_KEY_{:x}={}", scancode, key_name(key)).unwrap();
+            }
+        }
+        Ok(out)
+    }
+
+    /// Applies the mappings of every entry in `hwdb` that matches the device, in order, so later
+    /// entries win like they do in udev. Returns how many mappings were applied.
+    pub fn apply_hwdb(&mut self, hwdb: &Hwdb) -> Result<usize, ::nix::Error> {
+        let id = self.id;
+        let mut keymap = self.keymap();
+        let mut applied = 0;
+        for entry in hwdb.entries.iter().filter(|entry| entry.matches(&id)) {
+            for &(scancode, key) in &entry.keys {
+                keymap.set(scancode, key)?;
+                applied += 1;
+            }
+        }
+        Ok(applied)
+    }
+}
@@ -0,0 +1,237 @@
+//! Mappings from scancodes to keys.
+//!
+//! Keyboards identify the physical key that was pressed with a scancode (which many of them also
+//! report in `MSC_SCAN` events), and the driver translates that into a `Key` using a table that can
+//! be changed at runtime. That is how broken or unusual keys get fixed: find the key's scancode
+//! from `MSC_SCAN`, then map it to the right `Key` with `Keymap::set`. Changes last until the
+//! device is unplugged.
+
+use std::fmt;
+use std::os::unix::io::RawFd;
+
+use nix::{Errno, Error};
+
+use raw::*;
+use {Device, Key};
+
+/// A scancode, which is a driver-specific string of up to 32 bytes. Almost every driver uses
+/// 4-byte scancodes, which convert to and from `u32`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Scancode {
+    len: u8,
+    bytes: [u8; 32],
+}
+
+impl Scancode {
+    /// Returns `None` unless there are between 1 and 32 bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Scancode> {
+        if bytes.is_empty() || bytes.len() > 32 {
+            return None;
+        }
+        let mut scancode = Scancode { len: bytes.len() as u8, bytes: [0; 32] };
+        scancode.bytes[..bytes.len()].copy_from_slice(bytes);
+        Some(scancode)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+
+    /// The scancode as a number, if it is 1, 2 or 4 bytes long. Like the kernel, this reads the
+    /// bytes in native byte order.
+    pub fn to_u32(&self) -> Option<u32> {
+        let b = &self.bytes;
+        match self.len {
+            1 => Some(b[0] as u32),
+            2 => Some(u16::from_ne_bytes([b[0], b[1]]) as u32),
+            4 => Some(u32::from_ne_bytes([b[0], b[1], b[2], b[3]])),
+            _ => None,
+        }
+    }
+}
+
+impl From<u32> for Scancode {
+    fn from(code: u32) -> Scancode {
+        Scancode::from_bytes(&code.to_ne_bytes()).unwrap()
+    }
+}
+
+/// Lowercase hex: the number for scancodes that convert to `u32`, and the bytes in order for the
+/// rest.
+impl fmt::Display for Scancode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(code) = self.to_u32() {
+            return write!(f, "{:x}", code);
+        }
+        for byte in self.as_bytes() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Scancode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Scancode(0x{})", self)
+    }
+}
+
+/// One mapping in a keymap.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct KeymapEntry {
+    pub scancode: Scancode,
+    /// The code of the key the scancode maps to. See `key`.
+    pub keycode: u32,
+}
+
+impl KeymapEntry {
+    fn from_raw(entry: &input_keymap_entry) -> KeymapEntry {
+        let len = ::std::cmp::min(entry.len as usize, entry.scancode.len());
+        KeymapEntry {
+            scancode: Scancode::from_bytes(&entry.scancode[..len]).unwrap_or(Scancode::from(0)),
+            keycode: entry.keycode,
+        }
+    }
+
+    /// The key the scancode maps to, or `None` if the keycode is one `Key` doesn't know about.
+    /// Unmapped scancodes map to `KEY_RESERVED`.
+    pub fn key(&self) -> Option<Key> {
+        if self.keycode > u16::MAX as u32 {
+            return None;
+        }
+        Key::from_code(self.keycode as u16)
+    }
+}
+
+/// Whether an error from a `_V2` ioctl may just mean the kernel predates it (2.6.37), which
+/// rejects the unfamiliar request size with `EINVAL`.
+fn maybe_legacy(e: &Error) -> bool {
+    matches!(*e, Error::Sys(Errno::EINVAL) | Error::Sys(Errno::ENOTTY))
+}
+
+fn get_legacy(fd: RawFd, scancode: u32) -> Result<KeymapEntry, Error> {
+    let mut pair = [scancode, 0];
+    do_ioctl!(eviocgkeycode(fd, &mut pair));
+    Ok(KeymapEntry { scancode: Scancode::from(scancode), keycode: pair[1] })
+}
+
+fn get_by_index(fd: RawFd, index: u16) -> Result<KeymapEntry, Error> {
+    let mut entry = input_keymap_entry { flags: INPUT_KEYMAP_BY_INDEX, index, ..input_keymap_entry::default() };
+    do_ioctl!(eviocgkeycode_v2(fd, &mut entry));
+    Ok(KeymapEntry::from_raw(&entry))
+}
+
+/// The scancode to key mappings of a device, returned by `Device::keymap`.
+///
+/// Uses the `_V2` ioctls where the kernel has them, falling back to the legacy ones otherwise.
+/// The legacy ioctls only handle scancodes that convert to `u32`, and can only iterate over
+/// keymaps whose scancodes are contiguous from 0.
+#[derive(Debug)]
+pub struct Keymap<'a> {
+    dev: &'a mut Device,
+}
+
+impl<'a> Keymap<'a> {
+    /// Looks up the mapping for `scancode`. Fails with `EINVAL` if the driver doesn't know the
+    /// scancode.
+    pub fn get(&self, scancode: Scancode) -> Result<KeymapEntry, Error> {
+        let fd = self.dev.fd;
+        let mut entry = input_keymap_entry {
+            len: scancode.len,
+            scancode: scancode.bytes,
+            ..input_keymap_entry::default()
+        };
+        let res = unsafe { eviocgkeycode_v2(fd, &mut entry) };
+        match res {
+            Ok(_) => Ok(KeymapEntry::from_raw(&entry)),
+            Err(ref e) if maybe_legacy(e) && scancode.to_u32().is_some() =>
+                get_legacy(fd, scancode.to_u32().unwrap()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Looks up the mapping at position `index` in the keymap, or returns `None` if the keymap
+    /// isn't that long. Needs the `_V2` ioctls.
+    pub fn entry(&self, index: u16) -> Result<Option<KeymapEntry>, Error> {
+        match get_by_index(self.dev.fd, index) {
+            Ok(entry) => Ok(Some(entry)),
+            Err(Error::Sys(Errno::EINVAL)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Maps `scancode` to `key`. Use `KEY_RESERVED` to make the scancode do nothing.
+    pub fn set(&mut self, scancode: Scancode, key: Key) -> Result<(), Error> {
+        let fd = self.dev.fd;
+        let entry = input_keymap_entry {
+            len: scancode.len,
+            scancode: scancode.bytes,
+            keycode: key as u32,
+            ..input_keymap_entry::default()
+        };
+        let res = unsafe { eviocskeycode_v2(fd, &entry) };
+        match res {
+            Ok(_) => Ok(()),
+            Err(ref e) if maybe_legacy(e) && scancode.to_u32().is_some() => {
+                do_ioctl!(eviocskeycode(fd, &[scancode.to_u32().unwrap(), key as u32]));
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Iterates over every mapping in the keymap.
+    pub fn iter(&self) -> KeymapIter<'_> {
+        let fd = self.dev.fd;
+        let legacy = match get_by_index(fd, 0) {
+            Err(ref e) if maybe_legacy(e) => get_legacy(fd, 0).is_ok(),
+            _ => false,
+        };
+        KeymapIter { dev: self.dev, next: 0, legacy, done: false }
+    }
+}
+
+/// Iterator over the mappings of a keymap, returned by `Keymap::iter`. Stops after the first
+/// error.
+#[derive(Debug)]
+pub struct KeymapIter<'a> {
+    dev: &'a Device,
+    next: u32,
+    legacy: bool,
+    done: bool,
+}
+
+impl<'a> Iterator for KeymapIter<'a> {
+    type Item = Result<KeymapEntry, Error>;
+
+    fn next(&mut self) -> Option<Result<KeymapEntry, Error>> {
+        if self.done || self.next > u16::MAX as u32 {
+            return None;
+        }
+        let res = if self.legacy {
+            get_legacy(self.dev.fd, self.next)
+        } else {
+            get_by_index(self.dev.fd, self.next as u16)
+        };
+        self.next += 1;
+        match res {
+            Ok(entry) => Some(Ok(entry)),
+            // Both interfaces signal the end of the keymap this way.
+            Err(Error::Sys(Errno::EINVAL)) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl Device {
+    /// The device's scancode to key mappings.
+    pub fn keymap(&mut self) -> Keymap<'_> {
+        Keymap { dev: self }
+    }
+}
@@ -14,7 +14,8 @@
 //! This state can be queried. For example, the `DeviceState::led_vals` field will tell you which
 //! LEDs are currently lit on the device. This state is not automatically synchronized with the
 //! kernel. However, as the application reads events, this state will be updated if the event is
-//! newer than the state timestamp (maintained internally).  Additionally, you can call
+//! newer than the state timestamp (`DeviceState::timestamp`). Events are applied a frame at a
+//! time, once the `SYN_REPORT` that ends their frame has been read.  Additionally, you can call
 //! `Device::sync_state` to explicitly synchronize with the kernel state.
 //!
 //! As the state changes, the kernel will write events into a ring buffer. The application can read
@@ -48,7 +49,11 @@ use std::os::unix::io::*;
 use std::os::unix::ffi::*;
 use std::path::Path;
 use std::ffi::{CString, CStr};
-use std::mem::{size_of, transmute};
+use std::mem::{size_of, size_of_val};
+use std::collections::HashMap;
+use std::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use fixedbitset::FixedBitSet;
 
 use nix::Error;
@@ -141,166 +146,23 @@ bitflags! {
         /// Is a pointing stick ("clit mouse" etc, https://xkcd.com/243/)
         const POINTING_STICK = 1 << 0x05,
         /// Has an accelerometer. Probably reports relative events in that case?
-        const ACCELEROMETER = 1 << 0x06
+        const ACCELEROMETER = 1 << 0x06,
+        /// "pressure triggers clicks", according to the header of Linux 6.13. A touchpad without a
+        /// physical button, where pressing down hard enough counts as a click.
+        const PRESSUREPAD = 1 << 0x07,
     }
 }
 
-include!("scancodes.rs"); // it's a huge glob of text that I'm tired of skipping over.
-
-bitflags! {
-    pub flags RelativeAxis: u32 {
-        const REL_X = 1 << 0x00,
-        const REL_Y = 1 << 0x01,
-        const REL_Z = 1 << 0x02,
-        const REL_RX = 1 << 0x03,
-        const REL_RY = 1 << 0x04,
-        const REL_RZ = 1 << 0x05,
-        const REL_HWHEEL = 1 << 0x06,
-        const REL_DIAL = 1 << 0x07,
-        const REL_WHEEL = 1 << 0x08,
-        const REL_MISC = 1 << 0x09,
-    }
-}
-
-bitflags! {
-    pub flags AbsoluteAxis: u64 {
-        const ABS_X = 1 << 0x00,
-        const ABS_Y = 1 << 0x01,
-        const ABS_Z = 1 << 0x02,
-        const ABS_RX = 1 << 0x03,
-        const ABS_RY = 1 << 0x04,
-        const ABS_RZ = 1 << 0x05,
-        const ABS_THROTTLE = 1 << 0x06,
-        const ABS_RUDDER = 1 << 0x07,
-        const ABS_WHEEL = 1 << 0x08,
-        const ABS_GAS = 1 << 0x09,
-        const ABS_BRAKE = 1 << 0x0a,
-        const ABS_HAT0X = 1 << 0x10,
-        const ABS_HAT0Y = 1 << 0x11,
-        const ABS_HAT1X = 1 << 0x12,
-        const ABS_HAT1Y = 1 << 0x13,
-        const ABS_HAT2X = 1 << 0x14,
-        const ABS_HAT2Y = 1 << 0x15,
-        const ABS_HAT3X = 1 << 0x16,
-        const ABS_HAT3Y = 1 << 0x17,
-        const ABS_PRESSURE = 1 << 0x18,
-        const ABS_DISTANCE = 1 << 0x19,
-        const ABS_TILT_X = 1 << 0x1a,
-        const ABS_TILT_Y = 1 << 0x1b,
-        const ABS_TOOL_WIDTH = 1 << 0x1c,
-        const ABS_VOLUME = 1 << 0x20,
-        const ABS_MISC = 1 << 0x28,
-        /// "MT slot being modified"
-        const ABS_MT_SLOT = 1 << 0x2f,
-        /// "Major axis of touching ellipse"
-        const ABS_MT_TOUCH_MAJOR = 1 << 0x30,
-        /// "Minor axis (omit if circular)"
-        const ABS_MT_TOUCH_MINOR = 1 << 0x31,
-        /// "Major axis of approaching ellipse"
-        const ABS_MT_WIDTH_MAJOR = 1 << 0x32,
-        /// "Minor axis (omit if circular)"
-        const ABS_MT_WIDTH_MINOR = 1 << 0x33,
-        /// "Ellipse orientation"
-        const ABS_MT_ORIENTATION = 1 << 0x34,
-        /// "Center X touch position"
-        const ABS_MT_POSITION_X = 1 << 0x35,
-        /// "Center Y touch position"
-        const ABS_MT_POSITION_Y = 1 << 0x36,
-        /// "Type of touching device"
-        const ABS_MT_TOOL_TYPE = 1 << 0x37,
-        /// "Group a set of packets as a blob"
-        const ABS_MT_BLOB_ID = 1 << 0x38,
-        /// "Unique ID of the initiated contact"
-        const ABS_MT_TRACKING_ID = 1 << 0x39,
-        /// "Pressure on contact area"
-        const ABS_MT_PRESSURE = 1 << 0x3a,
-        /// "Contact over distance"
-        const ABS_MT_DISTANCE = 1 << 0x3b,
-        /// "Center X tool position"
-        const ABS_MT_TOOL_X = 1 << 0x3c,
-        /// "Center Y tool position"
-        const ABS_MT_TOOL_Y = 1 << 0x3d,
-    }
-}
+pub mod event;
+pub mod ff;
+pub mod hwdb;
+pub mod keymap;
+pub mod names;
+pub mod touch;
+pub mod uinput;
 
-bitflags! {
-    pub flags Switch: u32 {
-        /// "set = lid shut"
-        const SW_LID = 1 << 0x00,
-        /// "set = tablet mode"
-        const SW_TABLET_MODE = 1 << 0x01,
-        /// "set = inserted"
-        const SW_HEADPHONE_INSERT = 1 << 0x02,
-        /// "rfkill master switch, type 'any'"
-        const SW_RFKILL_ALL = 1 << 0x03,
-        /// "set = inserted"
-        const SW_MICROPHONE_INSERT = 1 << 0x04,
-        /// "set = plugged into doc"
-        const SW_DOCK = 1 << 0x05,
-        /// "set = inserted"
-        const SW_LINEOUT_INSERT = 1 << 0x06,
-        /// "set = mechanical switch set"
-        const SW_JACK_PHYSICAL_INSERT = 1 << 0x07,
-        /// "set  = inserted"
-        const SW_VIDEOOUT_INSERT = 1 << 0x08,
-        /// "set = lens covered"
-        const SW_CAMERA_LENS_COVER = 1 << 0x09,
-        /// "set = keypad slide out"
-        const SW_KEYPAD_SLIDE = 1 << 0x0a,
-        /// "set = front proximity sensor active"
-        const SW_FRONT_PROXIMITY = 1 << 0x0b,
-        /// "set = rotate locked/disabled"
-        const SW_ROTATE_LOCK = 1 << 0x0c,
-        /// "set = inserted"
-        const SW_LINEIN_INSERT = 1 << 0x0d,
-        /// "set = device disabled"
-        const SW_MUTE_DEVICE = 1 << 0x0e,
-        /// "set = pen inserted"
-        const SW_PEN_INSERTED = 1 << 0x0f,
-        const SW_MAX = 0xf,
-    }
-}
-
-bitflags! {
-    /// LEDs specified by USB HID.
-    pub flags Led: u32 {
-        const LED_NUML = 1 << 0x00,
-        const LED_CAPSL = 1 << 0x01,
-        const LED_SCROLLL = 1 << 0x02,
-        const LED_COMPOSE = 1 << 0x03,
-        const LED_KANA = 1 << 0x04,
-        /// "Stand-by"
-        const LED_SLEEP = 1 << 0x05,
-        const LED_SUSPEND = 1 << 0x06,
-        const LED_MUTE = 1 << 0x07,
-        /// "Generic indicator"
-        const LED_MISC = 1 << 0x08,
-        /// "Message waiting"
-        const LED_MAIL = 1 << 0x09,
-        /// "External power connected"
-        const LED_CHARGING = 1 << 0x0a,
-        const LED_MAX = 1 << 0x0f,
-    }
-}
-
-bitflags! {
-    /// Various miscellaneous event types. Current as of kernel 4.1.
-    pub flags Misc: u32 {
-        /// Serial number, only exported for tablets ("Transducer Serial Number")
-        const MSC_SERIAL = 1 << 0x00,
-        /// Only used by the PowerMate driver, right now.
-        const MSC_PULSELED = 1 << 0x01,
-        /// Completely unused.
-        const MSC_GESTURE = 1 << 0x02,
-        /// "Raw" event, rarely used.
-        const MSC_RAW = 1 << 0x03,
-        /// Key scancode
-        const MSC_SCAN = 1 << 0x04,
-        /// Completely unused.
-        const MSC_TIMESTAMP = 1 << 0x05,
-        const MSC_MAX = 1 << 0x07,
-    }
-}
+include!("scancodes.rs"); // it's a huge glob of text that I'm tired of skipping over.
+include!("codes.rs"); // generated by tools/gen-codes.py
 
 bitflags! {
     pub flags FFStatus: u32 {
@@ -310,7 +172,7 @@ bitflags! {
 }
 
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum FFEffect {
     FF_RUMBLE = 0x50,
     FF_PERIODIC = 0x51,
@@ -331,18 +193,27 @@ pub enum FFEffect {
     FF_MAX = 0x7f,
 }
 
-bitflags! {
-    pub flags Repeat: u32 {
-        const REP_DELAY = 1 << 0x00,
-        const REP_PERIOD = 1 << 0x01,
-    }
-}
-
-bitflags! {
-    pub flags Sound: u32 {
-        const SND_CLICK = 1 << 0x00,
-        const SND_BELL = 1 << 0x01,
-        const SND_TONE = 1 << 0x02,
+impl FFEffect {
+    fn from_code(code: usize) -> Option<FFEffect> {
+        Some(match code {
+            0x50 => FF_RUMBLE,
+            0x51 => FF_PERIODIC,
+            0x52 => FF_CONSTANT,
+            0x53 => FF_SPRING,
+            0x54 => FF_FRICTION,
+            0x55 => FF_DAMPER,
+            0x56 => FF_INERTIA,
+            0x57 => FF_RAMP,
+            0x58 => FF_SQUARE,
+            0x59 => FF_TRIANGLE,
+            0x5a => FF_SINE,
+            0x5b => FF_SAW_UP,
+            0x5c => FF_SAW_DOWN,
+            0x5d => FF_CUSTOM,
+            0x60 => FF_GAIN,
+            0x61 => FF_AUTOCENTER,
+            _ => return None,
+        })
     }
 }
 
@@ -377,6 +248,139 @@ pub enum Synchronization {
     SYN_DROPPED = 3,
 }
 
+/// A clock that event timestamps can be taken from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Clock {
+    /// Wall-clock time, which jumps whenever the system time is set. This is the default.
+    Realtime,
+    /// Time since an unspecified starting point, not counting time spent suspended. This is the
+    /// clock `std::time::Instant` uses.
+    Monotonic,
+    /// Like `Monotonic`, but counting time spent suspended.
+    Boottime,
+}
+
+impl Clock {
+    fn id(&self) -> libc::c_int {
+        match *self {
+            Clock::Realtime => libc::CLOCK_REALTIME,
+            Clock::Monotonic => libc::CLOCK_MONOTONIC,
+            Clock::Boottime => libc::CLOCK_BOOTTIME,
+        }
+    }
+
+    /// The current time according to this clock.
+    pub fn now(&self) -> EventTime {
+        let mut time = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        unsafe { clock_gettime(self.id(), &mut time); }
+        EventTime {
+            clock: *self,
+            since_origin: Duration::new(time.tv_sec as u64, time.tv_nsec as u32),
+        }
+    }
+}
+
+/// When an event happened, according to the `Clock` of the device it came from.
+///
+/// Times from the same clock can be subtracted to get the `Duration` between them. For the
+/// `Monotonic` clock that is the same arithmetic `std::time::Instant` does, so the two can be
+/// compared by way of `Clock::Monotonic.now()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EventTime {
+    clock: Clock,
+    since_origin: Duration,
+}
+
+impl EventTime {
+    /// Interprets `time` (as found in `input_event`) as a time on `clock`.
+    ///
+    /// The kernel never produces anything else, but `tv_usec` outside of `0..1_000_000` is carried
+    /// into the seconds, and times before the clock's starting point (a negative total) are taken
+    /// as the starting point itself.
+    pub fn new(clock: Clock, time: libc::timeval) -> EventTime {
+        let secs = time.tv_sec.saturating_add(time.tv_usec.div_euclid(1_000_000) as libc::time_t);
+        let since_origin = if secs < 0 {
+            Duration::default()
+        } else {
+            Duration::new(secs as u64, time.tv_usec.rem_euclid(1_000_000) as u32 * 1000)
+        };
+        EventTime { clock, since_origin }
+    }
+
+    pub fn clock(&self) -> Clock {
+        self.clock
+    }
+
+    /// Time since the clock's starting point: the Unix epoch for `Realtime`, and an unspecified
+    /// point (usually boot) for the others.
+    pub fn as_duration(&self) -> Duration {
+        self.since_origin
+    }
+
+    /// Time from `earlier` to `self`, or `None` if `earlier` is actually later or the two times
+    /// are from different clocks.
+    pub fn duration_since(&self, earlier: EventTime) -> Option<Duration> {
+        if self.clock != earlier.clock {
+            return None;
+        }
+        self.since_origin.checked_sub(earlier.since_origin)
+    }
+
+    /// Time from `self` until now, or zero if `self` is in the future.
+    pub fn elapsed(&self) -> Duration {
+        self.clock.now().duration_since(*self).unwrap_or_default()
+    }
+
+    /// The time as the kernel would stamp it. Only used for times from `new` and `Clock::now`, whose
+    /// seconds fit in a `time_t`.
+    fn to_timeval(self) -> libc::timeval {
+        libc::timeval {
+            tv_sec: self.since_origin.as_secs() as libc::time_t,
+            tv_usec: self.since_origin.subsec_micros() as libc::suseconds_t,
+        }
+    }
+}
+
+/// Errors from the few operations that can fail in ways a plain `nix::Error` doesn't describe:
+/// uploading force feedback effects, `try_grab`, and reading from a revoked device.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeviceError {
+    /// A system call failed, or the device doesn't support the operation.
+    Nix(Error),
+    /// The device can't hold any more force feedback effects until some are dropped. `max` is the
+    /// number of effects it can hold, which are shared between everyone using the device.
+    TooManyFFEffects { max: usize },
+    /// A custom force feedback waveform has more than `max` samples, see
+    /// `ff::FF_CUSTOM_MAX_SAMPLES`.
+    CustomWaveformTooLong { max: usize },
+    /// Access to the device was revoked with `Device::revoke`.
+    Revoked,
+    /// Another process (or another descriptor for the same device) has grabbed the device.
+    AlreadyGrabbed,
+}
+
+impl From<Error> for DeviceError {
+    fn from(e: Error) -> DeviceError {
+        DeviceError::Nix(e)
+    }
+}
+
+impl std::fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            DeviceError::Nix(ref e) => write!(f, "{}", e),
+            DeviceError::TooManyFFEffects { max } =>
+                write!(f, "no room for another force feedback effect (the device holds {})", max),
+            DeviceError::CustomWaveformTooLong { max } =>
+                write!(f, "custom force feedback waveform is longer than {} samples", max),
+            DeviceError::Revoked => write!(f, "access to the device has been revoked"),
+            DeviceError::AlreadyGrabbed => write!(f, "the device is already grabbed by someone else"),
+        }
+    }
+}
+
+impl std::error::Error for DeviceError {}
+
 #[derive(Clone)]
 pub struct DeviceState {
     /// The state corresponds to kernel state at this timestamp.
@@ -388,6 +392,165 @@ pub struct DeviceState {
     pub switch_vals: FixedBitSet,
     /// Set = LED lit
     pub led_vals: FixedBitSet,
+    /// Key repeat delay and period, if the device repeats keys.
+    pub rep_vals: Option<(Duration, Duration)>,
+    /// The `ABS_MT_*` values of each multitouch slot, indexed by slot and then by code less
+    /// `ABS_MT_TOUCH_MAJOR` (see `mt_value`). Empty unless the device has `ABS_MT_SLOT`; the
+    /// current slot is `abs_vals[ABS_MT_SLOT].value`.
+    pub mt_vals: Vec<[i32; MT_AXES]>,
+}
+
+/// How many `ABS_MT_*` codes there are after `ABS_MT_SLOT`, up to and including `ABS_MAX`.
+pub const MT_AXES: usize = 0x10;
+
+/// `code` as an index into the per-slot values of `DeviceState::mt_vals`, if it is an `ABS_MT_*`
+/// code other than `ABS_MT_SLOT`.
+fn mt_index(code: u16) -> Option<usize> {
+    let first = ABS_MT_SLOT.number::<u16>() + 1;
+    if code >= first && ((code - first) as usize) < MT_AXES {
+        Some((code - first) as usize)
+    } else {
+        None
+    }
+}
+
+/// How many `c_ulong`s the kernel uses for a bitmap of 64 bits, which is enough for every event
+/// type but keys and force feedback.
+const BITMAP_LONGS: usize = 64 / libc::c_ulong::BITS as usize;
+
+/// The bits in a bitmap filled in by the kernel, which is an array of `c_ulong`s: `u32`s or
+/// `u64`s depending on the target.
+fn bitmap_bits<T: Copy + Into<u64>>(bitmap: &[T]) -> u64 {
+    let width = 8 * std::mem::size_of::<T>();
+    bitmap.iter().enumerate().fold(0u64, |bits, (idx, &long)| bits | long.into() << (idx * width))
+}
+
+/// The codes of event type `ty` that the device supports, for any type but keys and force
+/// feedback. Type 0 gives the event types themselves.
+fn type_bits(fd: RawFd, ty: u32) -> Result<u64, Error> {
+    let mut bitmap = [0 as libc::c_ulong; BITMAP_LONGS];
+    do_ioctl!(eviocgbit(fd, ty, size_of_val(&bitmap) as libc::c_int, bitmap.as_mut_ptr() as *mut u8));
+    Ok(bitmap_bits(&bitmap))
+}
+
+/// The bytes of a bitmap, to have the kernel fill it in.
+fn as_bytes<T>(blocks: &mut [T]) -> &mut [u8] {
+    unsafe { std::slice::from_raw_parts_mut(blocks.as_mut_ptr() as *mut u8, size_of_val(blocks)) }
+}
+
+/// What the `resolution` of an absolute axis is measured against.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PhysicalUnit {
+    /// The resolution is in units per millimetre.
+    Millimeters,
+    /// The resolution is in units per radian.
+    Radians,
+}
+
+impl AbsoluteAxis {
+    /// The unit the kernel defines the resolution of this axis in, for the axes that have one:
+    /// positions and contact sizes in millimetres, rotations and tilt in radians.
+    ///
+    /// The axis ought to have only one flag set.
+    pub fn physical_unit(&self) -> Option<PhysicalUnit> {
+        let linear = ABS_X | ABS_Y | ABS_Z | ABS_MT_POSITION_X | ABS_MT_POSITION_Y | ABS_MT_TOOL_X |
+            ABS_MT_TOOL_Y | ABS_MT_TOUCH_MAJOR | ABS_MT_TOUCH_MINOR | ABS_MT_WIDTH_MAJOR |
+            ABS_MT_WIDTH_MINOR;
+        let angular = ABS_RX | ABS_RY | ABS_RZ | ABS_TILT_X | ABS_TILT_Y;
+        if linear.contains(*self) {
+            Some(PhysicalUnit::Millimeters)
+        } else if angular.contains(*self) {
+            Some(PhysicalUnit::Radians)
+        } else {
+            None
+        }
+    }
+}
+
+impl input_absinfo {
+    /// `value` scaled to [0, 1] over the axis' range. Values within `flat` of the minimum count as
+    /// the minimum (e.g. a trigger at rest), and the rest of the range is stretched to make up.
+    pub fn normalize(&self, value: i32) -> f64 {
+        let span = self.maximum as f64 - self.minimum as f64 - self.flat as f64;
+        let offset = value as f64 - self.minimum as f64 - self.flat as f64;
+        if span <= 0.0 || offset <= 0.0 {
+            return 0.0;
+        }
+        (offset / span).min(1.0)
+    }
+
+    /// `value` scaled to [-1, 1] over the axis' range, for axes that rest in the middle such as
+    /// joysticks. Values within `flat` of the middle count as 0, and the rest of the range is
+    /// stretched to make up.
+    pub fn normalize_centered(&self, value: i32) -> f64 {
+        let half = (self.maximum as f64 - self.minimum as f64) / 2.0;
+        let offset = value as f64 - (self.minimum as f64 + half);
+        let span = half - self.flat as f64;
+        if span <= 0.0 || offset.abs() <= self.flat as f64 {
+            return 0.0;
+        }
+        let scaled = (offset.abs() - self.flat as f64) / span;
+        scaled.min(1.0).copysign(offset)
+    }
+
+    /// `value` in physical units (see `AbsoluteAxis::physical_unit`), or `None` if the device
+    /// doesn't report a resolution.
+    pub fn to_physical(&self, value: i32) -> Option<f64> {
+        if self.resolution <= 0 {
+            return None;
+        }
+        Some(value as f64 / self.resolution as f64)
+    }
+}
+
+impl DeviceState {
+    /// The value of the multitouch axis `axis` in `slot`, if the device has that slot and axis.
+    /// A slot whose `ABS_MT_TRACKING_ID` is -1 isn't in use.
+    ///
+    /// `axis` ought to have only one flag set.
+    pub fn mt_value(&self, slot: usize, axis: AbsoluteAxis) -> Option<i32> {
+        let idx = mt_index(axis.number())?;
+        self.mt_vals.get(slot).map(|vals| vals[idx])
+    }
+
+    /// The parameters of `axis`, unless it has no range (which is how axes the device doesn't
+    /// have look). For `ABS_MT_*` axes, `value` is that of the current slot.
+    fn axis_info(&self, axis: AbsoluteAxis) -> Option<&input_absinfo> {
+        self.abs_vals.get(axis.number::<usize>()).filter(|info| info.minimum < info.maximum)
+    }
+
+    /// The current value of `axis`, scaled to [0, 1] as by `input_absinfo::normalize`.
+    pub fn abs_normalized(&self, axis: AbsoluteAxis) -> Option<f64> {
+        self.axis_info(axis).map(|info| info.normalize(info.value))
+    }
+
+    /// The current value of `axis`, scaled to [-1, 1] as by `input_absinfo::normalize_centered`.
+    pub fn abs_normalized_centered(&self, axis: AbsoluteAxis) -> Option<f64> {
+        self.axis_info(axis).map(|info| info.normalize_centered(info.value))
+    }
+
+    /// The current value of `axis` in physical units, if it has any and the device reports its
+    /// resolution.
+    pub fn abs_physical(&self, axis: AbsoluteAxis) -> Option<(f64, PhysicalUnit)> {
+        let info = self.axis_info(axis)?;
+        Some((info.to_physical(info.value)?, axis.physical_unit()?))
+    }
+
+    /// Like `abs_normalized`, for the value of the multitouch axis `axis` in `slot`.
+    pub fn mt_normalized(&self, slot: usize, axis: AbsoluteAxis) -> Option<f64> {
+        Some(self.axis_info(axis)?.normalize(self.mt_value(slot, axis)?))
+    }
+
+    /// Like `abs_normalized_centered`, for the value of the multitouch axis `axis` in `slot`.
+    pub fn mt_normalized_centered(&self, slot: usize, axis: AbsoluteAxis) -> Option<f64> {
+        Some(self.axis_info(axis)?.normalize_centered(self.mt_value(slot, axis)?))
+    }
+
+    /// Like `abs_physical`, for the value of the multitouch axis `axis` in `slot`.
+    pub fn mt_physical(&self, slot: usize, axis: AbsoluteAxis) -> Option<(f64, PhysicalUnit)> {
+        let value = self.axis_info(axis)?.to_physical(self.mt_value(slot, axis)?)?;
+        Some((value, axis.physical_unit()?))
+    }
 }
 
 pub struct Device {
@@ -406,14 +569,25 @@ pub struct Device {
     led: Led,
     misc: Misc,
     ff: FixedBitSet,
+    ff_effects_max: usize,
+    // Owns `fd`, and is shared with every `FFEffectHandle` uploaded through this device.
+    shared_fd: Arc<DeviceFd>,
     ff_stat: FFStatus,
+    // Last FF_STATUS reported for each effect id.
+    ff_effect_status: HashMap<i16, FFStatus>,
     rep: Repeat,
     snd: Sound,
     pending_events: Vec<input_event>,
-    clock: libc::c_int,
+    clock: Clock,
     // pending_events[last_seen..] is the events that have occurred since the last sync.
     last_seen: usize,
+    // pending_events[..handed_out] has been handed out by `RawEvents` or `Frames`, and
+    // pending_events[..frame_start] is dropped on the next read. The rest of what has been handed
+    // out is kept until its frame is complete, so that the frame can be applied to `state`.
+    handed_out: usize,
+    frame_start: usize,
     state: DeviceState,
+    revoked: bool,
 }
 
 impl std::fmt::Debug for Device {
@@ -450,6 +624,9 @@ impl std::fmt::Debug for Device {
                     ds.field(&format!("abs_{:x}", idx), &self.state.abs_vals[idx as usize]);
                 }
             }
+            if !self.state.mt_vals.is_empty() {
+                ds.field("mt_vals", &self.state.mt_vals);
+            }
         }
         if self.ty.contains(MISC) {
 
@@ -466,10 +643,12 @@ impl std::fmt::Debug for Device {
             ds.field("snd", &self.snd);
         }
         if self.ty.contains(REPEAT) {
-            ds.field("rep", &self.rep);
+            ds.field("rep", &self.rep)
+              .field("rep_vals", &self.state.rep_vals);
         }
         if self.ty.contains(FORCEFEEDBACK) {
-            ds.field("ff", &self.ff);
+            ds.field("ff", &self.ff)
+              .field("ff_effects_max", &self.ff_effects_max);
         }
         if self.ty.contains(POWER) {
         }
@@ -480,6 +659,12 @@ impl std::fmt::Debug for Device {
     }
 }
 
+/// `d` in whole milliseconds, saturating at the kernel's limit.
+fn to_millis(d: Duration) -> libc::c_uint {
+    let ms = d.as_secs().saturating_mul(1000).saturating_add(d.subsec_millis() as u64);
+    std::cmp::min(ms, libc::c_uint::MAX as u64) as libc::c_uint
+}
+
 fn bus_name(x: u16) -> &'static str {
     match x {
         0x1 => "PCI",
@@ -530,9 +715,8 @@ impl std::fmt::Display for Device {
             try!(writeln!(f, "  Keys supported:"));
             for key_idx in 0..self.key_bits.len() {
                 if self.key_bits.contains(key_idx) {
-                    // Cross our fingers... (what did this mean?)
-                    try!(writeln!(f, "    {:?} ({}index {})",
-                                 unsafe { std::mem::transmute::<_, Key>(key_idx as libc::c_int) },
+                    try!(writeln!(f, "    {} ({}index {})",
+                                 Key::from_code(key_idx as u16).map_or("unknown key", |key| key.name()),
                                  if self.state.key_vals.contains(key_idx) { "pressed, " } else { "" },
                                  key_idx));
                 }
@@ -559,9 +743,9 @@ impl std::fmt::Display for Device {
         }
         if self.ty.contains(SWITCH) {
             try!(writeln!(f, "  Switches:"));
-            for idx in 0..0xf {
+            for idx in 0..0x20 {
                 let sw = 1 << idx;
-                if sw < SW_MAX.bits() && self.switch.bits() & sw == 1 {
+                if self.switch.bits() & sw != 0 {
                     try!(writeln!(f, "    {:?} ({:?}, index {})",
                          Switch::from_bits(sw).unwrap(),
                          self.state.switch_vals[idx as usize],
@@ -571,9 +755,9 @@ impl std::fmt::Display for Device {
         }
         if self.ty.contains(LED) {
             try!(writeln!(f, "  LEDs:"));
-            for idx in 0..0xf {
+            for idx in 0..0x20 {
                 let led = 1 << idx;
-                if led < LED_MAX.bits() && self.led.bits() & led == 1 {
+                if self.led.bits() & led != 0 {
                     try!(writeln!(f, "    {:?} ({:?}, index {})",
                          Led::from_bits(led).unwrap(),
                          self.state.led_vals[idx as usize],
@@ -586,9 +770,13 @@ impl std::fmt::Display for Device {
         }
         if self.ty.contains(REPEAT) {
             try!(writeln!(f, "  Repeats: {:?}", self.rep));
+            if let Some((delay, period)) = self.state.rep_vals {
+                try!(writeln!(f, "    Delay: {}ms, period: {}ms", to_millis(delay), to_millis(period)));
+            }
         }
         if self.ty.contains(FORCEFEEDBACK) {
-            try!(writeln!(f, "  Force Feedback supported"));
+            try!(writeln!(f, "  Force Feedback: {:?} (up to {} effects at once)",
+                          self.ff_effects_supported(), self.ff_effects_max));
         }
         if self.ty.contains(POWER) {
             try!(writeln!(f, "  Power supported"));
@@ -600,7 +788,19 @@ impl std::fmt::Display for Device {
     }
 }
 
-impl Drop for Device {
+/// The descriptor of a `Device`, which is closed once the `Device` and every force feedback
+/// effect handle uploaded through it have been dropped.
+///
+/// Closing any descriptor for the device's open file, duplicates included, makes the kernel erase
+/// every effect uploaded through it. So the handles share this one instead of having their own.
+#[derive(Debug)]
+struct DeviceFd {
+    fd: RawFd,
+    /// How many `FFEffectHandle`s are alive.
+    ff_effects_in_use: AtomicUsize,
+}
+
+impl Drop for DeviceFd {
     fn drop(&mut self) {
         // Linux close(2) can fail, but there is nothing to do if it does.
         unsafe { libc::close(self.fd); }
@@ -652,6 +852,16 @@ impl Device {
         self.abs
     }
 
+    /// The parameters of `axis` as of the last synchronization, or `None` if the device doesn't
+    /// have it or `axis` isn't exactly one axis. For `ABS_MT_*` axes, `value` is that of the
+    /// current slot.
+    pub fn abs_info(&self, axis: AbsoluteAxis) -> Option<input_absinfo> {
+        if axis.bits().count_ones() != 1 || !self.abs.contains(axis) {
+            return None;
+        }
+        self.state.abs_vals.get(axis.number::<usize>()).cloned()
+    }
+
     pub fn switches_supported(&self) -> Switch {
         self.switch
     }
@@ -672,23 +882,37 @@ impl Device {
         self.snd
     }
 
+    /// The force feedback effects the device can play. `FF_GAIN` and `FF_AUTOCENTER` are included
+    /// if the device lets them be adjusted.
+    pub fn ff_effects_supported(&self) -> Vec<FFEffect> {
+        self.ff.ones().filter_map(FFEffect::from_code).collect()
+    }
+
+    /// How many force feedback effects the device can hold at once.
+    pub fn ff_effects_max(&self) -> usize {
+        self.ff_effects_max
+    }
+
+    /// Whether the effect with the given id is playing or stopped, as of the last `FF_STATUS`
+    /// event read from the device. `None` if the device hasn't reported on the effect since it was
+    /// uploaded; most devices never do.
+    pub fn ff_effect_status(&self, id: i16) -> Option<FFStatus> {
+        self.ff_effect_status.get(&id).cloned()
+    }
+
+    /// How many of the force feedback effects uploaded through this `Device` haven't been dropped
+    /// yet. Other users of the device may hold some of its effect slots as well.
+    pub fn ff_effects_in_use(&self) -> usize {
+        self.shared_fd.ff_effects_in_use.load(Ordering::SeqCst)
+    }
+
     pub fn state(&self) -> &DeviceState {
         &self.state
     }
 
-    pub fn open(path: &AsRef<Path>) -> Result<Device, Error> {
-        let cstr = match CString::new(path.as_ref().as_os_str().as_bytes()) {
-            Ok(s) => s,
-            Err(_) => return Err(Error::InvalidPath),
-        };
-        // FIXME: only need for writing is for setting LED values. re-evaluate always using RDWR
-        // later.
-        let fd = unsafe { libc::open(cstr.as_ptr(), libc::O_NONBLOCK | libc::O_RDWR | libc::O_CLOEXEC, 0) };
-        if fd == -1 {
-            return Err(Error::from_errno(::nix::Errno::last()));
-        }
-
-        let mut dev = Device {
+    /// A `Device` for `fd` that doesn't support anything yet; `open` fills in the rest.
+    fn new(fd: RawFd) -> Device {
+        Device {
             fd: fd,
             ty: Types::empty(),
             name: unsafe { CString::from_vec_unchecked(Vec::new()) },
@@ -697,34 +921,55 @@ impl Device {
             id: unsafe { std::mem::zeroed() },
             props: Props::empty(),
             driver_version: (0, 0, 0),
-            key_bits: FixedBitSet::with_capacity(KEY_MAX as usize),
+            key_bits: FixedBitSet::with_capacity(KEY_MAX as usize + 1),
             rel: RelativeAxis::empty(),
             abs: AbsoluteAxis::empty(),
             switch: Switch::empty(),
             led: Led::empty(),
             misc: Misc::empty(),
             ff: FixedBitSet::with_capacity(FF_MAX as usize + 1),
+            ff_effects_max: 0,
+            shared_fd: Arc::new(DeviceFd { fd, ff_effects_in_use: AtomicUsize::new(0) }),
             ff_stat: FFStatus::empty(),
+            ff_effect_status: HashMap::new(),
             rep: Repeat::empty(),
             snd: Sound::empty(),
             pending_events: Vec::with_capacity(64),
             last_seen: 0,
+            handed_out: 0,
+            frame_start: 0,
             state: DeviceState {
                 timestamp: libc::timeval { tv_sec: 0, tv_usec: 0 },
-                key_vals: FixedBitSet::with_capacity(KEY_MAX as usize),
+                key_vals: FixedBitSet::with_capacity(KEY_MAX as usize + 1),
                 abs_vals: vec![],
-                switch_vals: FixedBitSet::with_capacity(0x10),
-                led_vals: FixedBitSet::with_capacity(0x10),
+                switch_vals: FixedBitSet::with_capacity(0x20),
+                led_vals: FixedBitSet::with_capacity(0x20),
+                rep_vals: None,
+                mt_vals: vec![],
             },
-            clock: libc::CLOCK_REALTIME
+            clock: Clock::Realtime,
+            revoked: false,
+        }
+    }
+
+    pub fn open(path: &AsRef<Path>) -> Result<Device, Error> {
+        let cstr = match CString::new(path.as_ref().as_os_str().as_bytes()) {
+            Ok(s) => s,
+            Err(_) => return Err(Error::InvalidPath),
         };
+        // Write access is needed for `write_events`, which sets LEDs, makes sounds and so on.
+        let fd = unsafe { libc::open(cstr.as_ptr(), libc::O_NONBLOCK | libc::O_RDWR | libc::O_CLOEXEC, 0) };
+        if fd == -1 {
+            return Err(Error::from_errno(::nix::Errno::last()));
+        }
+
+        let mut dev = Device::new(fd);
 
-        let mut bits: u32 = 0;
-        let mut bits64: u64 = 0;
         let mut buf = [0u8; 256];
 
-        do_ioctl!(eviocgbit(fd, 0, 4, &mut bits as *mut u32 as *mut u8));
-        dev.ty = Types::from_bits(bits).expect("evdev: unexpected type bits! report a bug");
+        // Unknown bits are dropped rather than treated as an error, so that devices using codes
+        // newer than this crate still open.
+        dev.ty = Types::from_bits_truncate(type_bits(fd, 0)? as u32);
 
         dev.name = do_ioctl_buf!(buf, eviocgname, fd).unwrap_or(CString::default());
         dev.phys = do_ioctl_buf!(buf, eviocgphys, fd);
@@ -738,47 +983,48 @@ impl Device {
              ((driver_version >> 8) & 0xff) as u8,
               (driver_version & 0xff) as u8);
 
-        do_ioctl!(eviocgprop(fd, std::slice::from_raw_parts_mut(&mut bits as *mut u32 as *mut u8, 0x1f))); // FIXME: handle old kernel
-        dev.props = Props::from_bits(bits).expect("evdev: unexpected prop bits! report a bug");
+        let mut props = [0 as libc::c_ulong; BITMAP_LONGS];
+        do_ioctl!(eviocgprop(fd, as_bytes(&mut props))); // FIXME: handle old kernel
+        dev.props = Props::from_bits_truncate(bitmap_bits(&props) as u32);
 
         if dev.ty.contains(KEY) {
-            do_ioctl!(eviocgbit(fd, KEY.number(), dev.key_bits.len() as libc::c_int, dev.key_bits.as_mut_slice().as_mut_ptr() as *mut u8));
+            do_ioctl!(eviocgbit(fd, KEY.number(), size_of_val(dev.key_bits.as_slice()) as libc::c_int, dev.key_bits.as_mut_slice().as_mut_ptr() as *mut u8));
         }
 
         if dev.ty.contains(RELATIVE) {
-            do_ioctl!(eviocgbit(fd, RELATIVE.number(), 0xf, &mut bits as *mut u32 as *mut u8));
-            dev.rel = RelativeAxis::from_bits(bits).expect("evdev: unexpected rel bits! report a bug");
+            dev.rel = RelativeAxis::from_bits_truncate(type_bits(fd, RELATIVE.number())? as u32);
         }
 
         if dev.ty.contains(ABSOLUTE) {
-            do_ioctl!(eviocgbit(fd, ABSOLUTE.number(), 0x3f, &mut bits64 as *mut u64 as *mut u8));
-            dev.abs = AbsoluteAxis::from_bits(bits64).expect("evdev: unexpected abs bits! report a bug");
+            dev.abs = AbsoluteAxis::from_bits_truncate(type_bits(fd, ABSOLUTE.number())?);
             dev.state.abs_vals = vec![input_absinfo::default(); 0x3f];
         }
 
         if dev.ty.contains(SWITCH) {
-            do_ioctl!(eviocgbit(fd, SWITCH.number(), 0xf, &mut bits as *mut u32 as *mut u8));
-            dev.switch = Switch::from_bits(bits).expect("evdev: unexpected switch bits! report a bug");
+            dev.switch = Switch::from_bits_truncate(type_bits(fd, SWITCH.number())? as u32);
         }
 
         if dev.ty.contains(LED) {
-            do_ioctl!(eviocgbit(fd, LED.number(), 0xf, &mut bits as *mut u32 as *mut u8));
-            dev.led = Led::from_bits(bits).expect("evdev: unexpected led bits! report a bug");
+            dev.led = Led::from_bits_truncate(type_bits(fd, LED.number())? as u32);
         }
 
         if dev.ty.contains(MISC) {
-            do_ioctl!(eviocgbit(fd, MISC.number(), 0x7, &mut bits as *mut u32 as *mut u8));
-            dev.misc = Misc::from_bits(bits).expect("evdev: unexpected misc bits! report a bug");
+            dev.misc = Misc::from_bits_truncate(type_bits(fd, MISC.number())? as u32);
         }
 
-        //do_ioctl!(eviocgbit(fd, ffs(FORCEFEEDBACK.bits()), 0x7f, &mut bits as *mut u32 as *mut u8));
+        if dev.ty.contains(FORCEFEEDBACK) {
+            let len = std::mem::size_of_val(dev.ff.as_slice());
+            do_ioctl!(eviocgbit(fd, FORCEFEEDBACK.number(), len as libc::c_int, dev.ff.as_mut_slice().as_mut_ptr() as *mut u8));
+            let mut max: libc::c_int = 0;
+            do_ioctl!(eviocgeffects(fd, &mut max));
+            dev.ff_effects_max = max as usize;
+        }
 
         if dev.ty.contains(SOUND) {
-            do_ioctl!(eviocgbit(fd, SOUND.number(), 0x7, &mut bits as *mut u32 as *mut u8));
-            dev.snd = Sound::from_bits(bits).expect("evdev: unexpected sound bits! report a bug");
+            dev.snd = Sound::from_bits_truncate(type_bits(fd, SOUND.number())? as u32);
         }
 
-        try!(dev.sync_state());
+        try!(dev.read_state());
 
         Ok(dev)
     }
@@ -786,37 +1032,315 @@ impl Device {
     /// Synchronize the `Device` state with the kernel device state.
     ///
     /// If there is an error at any point, the state will not be synchronized completely.
-    pub fn sync_state(&mut self) -> Result<(), Error> {
+    pub fn sync_state(&mut self) -> Result<(), DeviceError> {
+        if self.revoked {
+            return Err(DeviceError::Revoked);
+        }
+        Ok(self.read_state()?)
+    }
+
+    fn read_state(&mut self) -> Result<(), Error> {
         if self.ty.contains(KEY) {
-            do_ioctl!(eviocgkey(self.fd, transmute::<&mut [u32], &mut [u8]>(self.state.key_vals.as_mut_slice())));
+            do_ioctl!(eviocgkey(self.fd, as_bytes(self.state.key_vals.as_mut_slice())));
         }
         if self.ty.contains(ABSOLUTE) {
-            for idx in 0..0x28 {
+            for idx in 0..0x3f {
                 let abs = 1 << idx;
-                // ignore multitouch, we'll handle that later.
-                if abs < ABS_MT_SLOT.bits() && self.abs.bits() & abs != 0 {
+                // For the ABS_MT_* axes, this reads the value in the current slot.
+                if self.abs.bits() & abs != 0 {
                     do_ioctl!(eviocgabs(self.fd, idx as u32, &mut self.state.abs_vals[idx as usize]));
                 }
             }
+            if self.abs.contains(ABS_MT_SLOT) {
+                self.sync_mt_slots()?;
+            }
         }
         if self.ty.contains(SWITCH) {
-            do_ioctl!(eviocgsw(self.fd, transmute::<&mut [u32], &mut [u8]>(self.state.switch_vals.as_mut_slice())));
+            do_ioctl!(eviocgsw(self.fd, as_bytes(self.state.switch_vals.as_mut_slice())));
         }
         if self.ty.contains(LED) {
-            do_ioctl!(eviocgled(self.fd, transmute::<&mut [u32], &mut [u8]>(self.state.led_vals.as_mut_slice())));
+            self.sync_leds()?;
+        }
+        if self.ty.contains(REPEAT) {
+            self.state.rep_vals = Some(self.repeat_settings()?);
+        }
+        self.state.timestamp = self.clock.now().to_timeval();
+
+        Ok(())
+    }
+
+    fn sync_mt_slots(&mut self) -> Result<(), Error> {
+        let slots = (self.state.abs_vals[ABS_MT_SLOT.number::<usize>()].maximum + 1).max(0) as usize;
+        self.state.mt_vals = vec![[0; MT_AXES]; slots];
+        // EVIOCGMTSLOTS takes the code to read in the first element, and fills in the rest with
+        // one value per slot.
+        let mut buf = vec![0i32; slots + 1];
+        for code in ABS_MT_SLOT.number::<u16>() + 1..0x40 {
+            let idx = match mt_index(code) {
+                Some(idx) if self.abs.bits() & 1 << code != 0 => idx,
+                _ => continue,
+            };
+            buf[0] = code as i32;
+            let len = std::mem::size_of_val(&buf[..]);
+            do_ioctl!(eviocgmtslots(self.fd, std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, len)));
+            for (slot, &value) in buf[1..].iter().enumerate() {
+                self.state.mt_vals[slot][idx] = value;
+            }
+        }
+        Ok(())
+    }
+
+    fn sync_leds(&mut self) -> Result<(), Error> {
+        do_ioctl!(eviocgled(self.fd, as_bytes(self.state.led_vals.as_mut_slice())));
+        Ok(())
+    }
+
+    /// The LEDs that are lit, according to `DeviceState::led_vals`.
+    fn lit_leds(&self) -> Led {
+        let bits = (0..0x10).filter(|&idx| self.state.led_vals[idx]).fold(0, |bits, idx| bits | 1 << idx);
+        Led::from_bits_truncate(bits)
+    }
+
+    /// Turns the LEDs in `mask` on if they are in `leds` and off otherwise, skipping any the device
+    /// doesn't have, then reads back what the kernel actually did.
+    fn update_leds(&mut self, leds: Led, mask: Led) -> Result<(), Error> {
+        let mut events = Vec::new();
+        for idx in 0..0x10 {
+            let led = Led::from_bits_truncate(1 << idx);
+            if !led.is_empty() && (mask & self.led).contains(led) {
+                events.push(input_event {
+                    _type: LED.number(),
+                    code: idx,
+                    value: if leds.contains(led) { 1 } else { 0 },
+                    ..input_event::default()
+                });
+            }
+        }
+        self.write_events(&events)?;
+        self.sync_leds()
+    }
+
+    /// Turns `led` (which may contain several LEDs) on or off.
+    ///
+    /// `DeviceState::led_vals` is updated from the kernel afterwards, so it reflects whether the
+    /// change actually took effect. LEDs the device doesn't have are ignored.
+    pub fn set_led(&mut self, led: Led, on: bool) -> Result<(), Error> {
+        self.update_leds(if on { led } else { Led::empty() }, led)
+    }
+
+    /// Lights exactly the LEDs in `leds`, turning every other LED of the device off.
+    ///
+    /// `DeviceState::led_vals` is updated from the kernel afterwards, as with `set_led`.
+    pub fn set_leds(&mut self, leds: Led) -> Result<(), Error> {
+        let all = self.led;
+        self.update_leds(leds, all)
+    }
+
+    /// Grabs the device, so that its events are delivered to this `Device` only: nothing else
+    /// reading the device (including the console and display servers) sees them until the
+    /// returned guard is dropped, which releases the grab again.
+    ///
+    /// Fails with `EBUSY` if the device is already grabbed; see `try_grab`.
+    pub fn grab(&mut self) -> Result<GrabGuard<'_>, Error> {
+        do_ioctl!(eviocgrab(self.fd, 1));
+        Ok(GrabGuard(self))
+    }
+
+    /// Like `grab`, but fails with `AlreadyGrabbed` rather than `EBUSY` if something else already
+    /// holds the grab.
+    pub fn try_grab(&mut self) -> Result<GrabGuard<'_>, DeviceError> {
+        match self.grab() {
+            Err(Error::Sys(::nix::Errno::EBUSY)) => Err(DeviceError::AlreadyGrabbed),
+            res => Ok(res?),
+        }
+    }
+
+    /// Permanently revokes access to the device through this `Device` (and the force feedback
+    /// effect handles uploaded through it, which share its descriptor). Anything else that has
+    /// the device open is unaffected.
+    ///
+    /// Afterwards, reading events and synchronizing state fail with `Revoked`, and any other
+    /// operation fails with `ENODEV`. This is meant for handing devices over between
+    /// sessions, e.g. when a compositor switches away from its VT.
+    ///
+    /// Reads only fail with `Revoked` after this `Device` revoked itself. When the descriptor is
+    /// revoked elsewhere (e.g. by logind, which hands out descriptors sharing its own), reads fail
+    /// with `ENODEV` instead: the kernel reports that the same way as the device being unplugged,
+    /// so the two can't be told apart.
+    pub fn revoke(&mut self) -> Result<(), Error> {
+        do_ioctl!(eviocrevoke(self.fd, 0));
+        self.revoked = true;
+        Ok(())
+    }
+
+    pub fn is_revoked(&self) -> bool {
+        self.revoked
+    }
+
+    /// The clock the device's events are timestamped with.
+    pub fn clock(&self) -> Clock {
+        self.clock
+    }
+
+    /// Switches the clock the kernel timestamps the device's events with. The events `events`
+    /// inserts to make up for a `SYN_DROPPED` are stamped with the same clock.
+    ///
+    /// Events already buffered by the kernel are discarded when the clock changes, and a
+    /// `SYN_DROPPED` takes their place. So are the events already read into this `Device` but not
+    /// handed out yet, whose timestamps can't be compared with the new clock's; the state is
+    /// brought up to date when the `SYN_DROPPED` is read.
+    pub fn set_clock(&mut self, clock: Clock) -> Result<(), Error> {
+        do_ioctl!(eviocsclockid(self.fd, &clock.id()));
+        self.clock_switched(clock);
+        Ok(())
+    }
+
+    /// Catches up with the kernel having switched to `clock`, see `set_clock`.
+    fn clock_switched(&mut self, clock: Clock) {
+        if clock != self.clock {
+            self.clock = clock;
+            self.pending_events.clear();
+            self.handed_out = 0;
+            self.frame_start = 0;
+            self.last_seen = 0;
+            // Keep the state timestamp comparable with the timestamps of new events.
+            self.state.timestamp = clock.now().to_timeval();
         }
+    }
+
+    /// The time `event`, which must have been read from this device, happened at.
+    pub fn event_time(&self, event: &input_event) -> EventTime {
+        EventTime::new(self.clock, event.time)
+    }
+
+    /// The key repeat settings: how long a key has to be held before it starts repeating, and
+    /// then how long between repeats.
+    pub fn repeat_settings(&self) -> Result<(Duration, Duration), Error> {
+        let mut rep = [0; 2];
+        do_ioctl!(eviocgrep(self.fd, &mut rep));
+        Ok((Duration::from_millis(rep[0] as u64), Duration::from_millis(rep[1] as u64)))
+    }
+
+    /// Changes the key repeat settings (see `repeat_settings`). The kernel works in whole
+    /// milliseconds, so anything finer is truncated.
+    pub fn set_repeat_settings(&mut self, delay: Duration, period: Duration) -> Result<(), Error> {
+        let rep = [to_millis(delay), to_millis(period)];
+        do_ioctl!(eviocsrep(self.fd, &rep));
+        self.state.rep_vals = Some(self.repeat_settings()?);
+        Ok(())
+    }
 
+    /// Replaces the parameters of `axis` (its range, fuzz, flat, resolution and current value),
+    /// e.g. to apply a calibration. `DeviceState::abs_vals` is read back from the kernel
+    /// afterwards. The change affects everything that uses the device, until it is unplugged.
+    ///
+    /// Fails with `UnsupportedOperation` if `axis` isn't exactly one axis or the device doesn't
+    /// have it, and with `EINVAL` for `ABS_MT_SLOT`, whose range is fixed.
+    pub fn set_abs_info(&mut self, axis: AbsoluteAxis, info: input_absinfo) -> Result<(), Error> {
+        if axis.bits().count_ones() != 1 || !self.abs.contains(axis) {
+            return Err(Error::UnsupportedOperation);
+        }
+        let idx = axis.number::<u32>();
+        do_ioctl!(eviocsabs(self.fd, idx, &info));
+        do_ioctl!(eviocgabs(self.fd, idx, &mut self.state.abs_vals[idx as usize]));
         Ok(())
     }
 
+    fn ff_control(&mut self, control: FFEffect, value: u16) -> Result<(), Error> {
+        if !self.ff.contains(control as usize) {
+            return Err(Error::UnsupportedOperation);
+        }
+        self.write_event(FORCEFEEDBACK, control as u16, value as i32)
+    }
+
+    /// Sets the strength of all force feedback effects, from 0 (off) to 0xffff (full strength).
+    ///
+    /// Fails with `UnsupportedOperation` if the device doesn't support `FF_GAIN`.
+    pub fn set_ff_gain(&mut self, gain: u16) -> Result<(), Error> {
+        self.ff_control(FF_GAIN, gain)
+    }
+
+    /// Sets the strength of the force that pulls the device back to its center, from 0 (off) to
+    /// 0xffff (full strength).
+    ///
+    /// Fails with `UnsupportedOperation` if the device doesn't support `FF_AUTOCENTER`.
+    pub fn set_ff_autocenter(&mut self, strength: u16) -> Result<(), Error> {
+        self.ff_control(FF_AUTOCENTER, strength)
+    }
+
+    fn sound(&mut self, snd: Sound, value: i32) -> Result<(), Error> {
+        if !self.snd.contains(snd) {
+            return Err(Error::UnsupportedOperation);
+        }
+        self.write_event(SOUND, snd.number(), value)
+    }
+
+    /// Rings the bell for 125ms (the console's default bell length), blocking until it has
+    /// stopped. See `bell_for` to choose the length.
+    ///
+    /// Fails with `UnsupportedOperation` if the device has no `SND_BELL`.
+    pub fn bell(&mut self) -> Result<(), Error> {
+        self.bell_for(std::time::Duration::from_millis(125))
+    }
+
+    /// Rings the bell for `duration`, blocking until it has stopped.
+    ///
+    /// Fails with `UnsupportedOperation` if the device has no `SND_BELL`.
+    pub fn bell_for(&mut self, duration: std::time::Duration) -> Result<(), Error> {
+        self.sound(SND_BELL, 1)?;
+        std::thread::sleep(duration);
+        self.sound(SND_BELL, 0)
+    }
+
+    /// Makes a single key click.
+    ///
+    /// Fails with `UnsupportedOperation` if the device has no `SND_CLICK`.
+    pub fn click(&mut self) -> Result<(), Error> {
+        self.sound(SND_CLICK, 1)?;
+        self.sound(SND_CLICK, 0)
+    }
+
+    /// Starts playing a tone of `hz` hertz, which keeps playing until it is replaced by another
+    /// tone. A frequency of 0 stops the tone.
+    ///
+    /// Fails with `UnsupportedOperation` if the device has no `SND_TONE`.
+    pub fn tone(&mut self, hz: u32) -> Result<(), Error> {
+        self.sound(SND_TONE, std::cmp::min(hz, i32::MAX as u32) as i32)
+    }
+
+    /// Plays a tone of `hz` hertz for `duration`, blocking until it has stopped.
+    pub fn beep(&mut self, hz: u32, duration: std::time::Duration) -> Result<(), Error> {
+        self.tone(hz)?;
+        std::thread::sleep(duration);
+        self.tone(0)
+    }
+
+    /// The state the application is left in once it has been handed the complete frames still
+    /// pending. After a `SYN_DROPPED`, those are the frames before it, so the deltas to the kernel
+    /// state have to start from here. The frames are stale after the sync, so they won't be applied
+    /// to `self.state` twice.
+    fn state_after_pending(&self) -> DeviceState {
+        let mut state = self.state.clone();
+        state.timestamp = libc::timeval { tv_sec: 0, tv_usec: 0 };
+        let mut ff_effect_status = HashMap::new();
+        let mut start = self.frame_start;
+        for end in self.frame_start..self.pending_events.len() {
+            if is_syn_report(&self.pending_events[end]) {
+                apply_frame(&mut state, &mut ff_effect_status, &self.pending_events[start..=end]);
+                start = end + 1;
+            }
+        }
+        state
+    }
+
     /// Do SYN_DROPPED synchronization, and compensate for missing events by inserting events into
     /// the stream which, when applied to any state being kept outside of this `Device`, will
     /// synchronize it with the kernel state.
     fn compensate_dropped(&mut self) -> Result<(), Error> {
         let mut drop_from = None;
         for (idx, event) in self.pending_events[self.last_seen..].iter().enumerate() {
-            if event._type == SYN_DROPPED as u16 {
-                drop_from = Some(idx);
+            if event._type == SYNCHRONIZATION.number::<u16>() && event.code == SYN_DROPPED as u16 {
+                drop_from = Some(self.last_seen + idx);
                 break
             }
         }
@@ -826,12 +1350,14 @@ impl Device {
             // look for the nearest SYN_REPORT before the SYN_DROPPED, remove everything after it.
             let mut prev_report = 0; // (if there's no previous SYN_REPORT, then the entire vector is bogus)
             for (idx, event) in self.pending_events[..idx].iter().enumerate().rev() {
-                if event._type == SYN_REPORT as u16 {
-                    prev_report = idx;
+                if event._type == SYNCHRONIZATION.number::<u16>() && event.code == SYN_REPORT as u16 {
+                    prev_report = idx + 1;
                     break;
                 }
             }
             self.pending_events.truncate(prev_report);
+            self.handed_out = self.handed_out.min(prev_report);
+            self.frame_start = self.frame_start.min(prev_report);
         } else {
             return Ok(())
         }
@@ -839,14 +1365,9 @@ impl Device {
         // Alright, pending_events is in a sane state. Now, let's sync the local state. We will
         // create a phony packet that contains deltas from the previous device state to the current
         // device state.
-        let old_state = self.state.clone();
-        try!(self.sync_state());
-        let mut time = unsafe { std::mem::zeroed() };
-        unsafe { clock_gettime(self.clock, &mut time); }
-        let time = libc::timeval {
-            tv_sec: time.tv_sec,
-            tv_usec: time.tv_nsec / 1000,
-        };
+        let old_state = self.state_after_pending();
+        try!(self.read_state());
+        let time = self.clock.now().to_timeval();
 
         if self.ty.contains(KEY) {
             for key_idx in 0..self.key_bits.len() {
@@ -862,9 +1383,14 @@ impl Device {
                 }
             }
         }
+        let has_slots = self.abs.contains(ABS_MT_SLOT);
         if self.ty.contains(ABSOLUTE) {
             for idx in 0..0x3f {
                 let abs = 1 << idx;
+                // With slots, the ABS_MT_* axes are handled per slot below.
+                if has_slots && abs >= ABS_MT_SLOT.bits() {
+                    continue;
+                }
                 if self.abs.bits() & abs != 0 {
                     if old_state.abs_vals[idx as usize] != self.state.abs_vals[idx as usize] {
                         self.pending_events.push(raw::input_event {
@@ -877,10 +1403,48 @@ impl Device {
                 }
             }
         }
+        if has_slots {
+            let slot_code = ABS_MT_SLOT.number::<u16>();
+            let mut slot = old_state.abs_vals[slot_code as usize].value;
+            for (new_slot, vals) in self.state.mt_vals.iter().enumerate() {
+                let old_vals = old_state.mt_vals.get(new_slot).cloned().unwrap_or([0; MT_AXES]);
+                for (idx, &value) in vals.iter().enumerate() {
+                    let code = slot_code + 1 + idx as u16;
+                    if value == old_vals[idx] || self.abs.bits() & 1 << code == 0 {
+                        continue;
+                    }
+                    if slot != new_slot as i32 {
+                        slot = new_slot as i32;
+                        self.pending_events.push(raw::input_event {
+                            time,
+                            _type: ABSOLUTE.number(),
+                            code: slot_code,
+                            value: slot,
+                        });
+                    }
+                    self.pending_events.push(raw::input_event {
+                        time,
+                        _type: ABSOLUTE.number(),
+                        code,
+                        value,
+                    });
+                }
+            }
+            // Leave the reader in the kernel's current slot, which later events assume.
+            let current = self.state.abs_vals[slot_code as usize].value;
+            if slot != current {
+                self.pending_events.push(raw::input_event {
+                    time,
+                    _type: ABSOLUTE.number(),
+                    code: slot_code,
+                    value: current,
+                });
+            }
+        }
         if self.ty.contains(SWITCH) {
-            for idx in 0..0xf {
+            for idx in 0..0x20 {
                 let sw = 1 << idx;
-                if sw < SW_MAX.bits() && self.switch.bits() & sw == 1 {
+                if self.switch.bits() & sw != 0 {
                     if old_state.switch_vals[idx as usize] != self.state.switch_vals[idx as usize] {
                         self.pending_events.push(raw::input_event {
                             time: time,
@@ -893,9 +1457,9 @@ impl Device {
             }
         }
         if self.ty.contains(LED) {
-            for idx in 0..0xf {
+            for idx in 0..0x20 {
                 let led = 1 << idx;
-                if led < LED_MAX.bits() && self.led.bits() & led == 1 {
+                if self.led.bits() & led != 0 {
                     if old_state.led_vals[idx as usize] != self.state.led_vals[idx as usize] {
                         self.pending_events.push(raw::input_event {
                             time: time,
@@ -917,7 +1481,14 @@ impl Device {
         Ok(())
     }
 
-    fn fill_events(&mut self) -> Result<(), Error> {
+    fn fill_events(&mut self) -> Result<(), DeviceError> {
+        if self.revoked {
+            return Err(DeviceError::Revoked);
+        }
+        self.pending_events.drain(..self.frame_start);
+        self.handed_out -= self.frame_start;
+        self.last_seen = self.last_seen.saturating_sub(self.frame_start);
+        self.frame_start = 0;
         let buf = &mut self.pending_events;
         loop {
             buf.reserve(20);
@@ -931,7 +1502,7 @@ impl Device {
             if sz == -1 {
                 let errno = ::nix::Errno::last();
                 if errno != ::nix::Errno::EAGAIN {
-                    return Err(Error::from_errno(errno));
+                    return Err(Error::from_errno(errno).into());
                 } else {
                     break;
                 }
@@ -944,8 +1515,30 @@ impl Device {
         Ok(())
     }
 
+    /// Writes `events` to the device, terminated with a `SYN_REPORT` if they aren't already.
+    ///
+    /// The events go to the kernel's input core as if the device had produced them: LED and sound
+    /// events are passed on to the hardware, and every reader of the device (including this one)
+    /// receives the events that the device supports. Events the device doesn't support are
+    /// dropped silently.
+    pub fn write_events(&mut self, events: &[input_event]) -> Result<(), Error> {
+        write_frame(self.fd, events)
+    }
+
+    /// Writes a single event of type `ty` to the device, followed by a `SYN_REPORT`.
+    ///
+    /// `ty` ought to have only one flag set. See `write_events`.
+    pub fn write_event(&mut self, ty: Types, code: u16, value: i32) -> Result<(), Error> {
+        self.write_events(&[input_event {
+            _type: ty.number(),
+            code,
+            value,
+            ..input_event::default()
+        }])
+    }
+
     /// Exposes the raw evdev events without doing synchronization on SYN_DROPPED.
-    pub fn events_no_sync(&mut self) -> Result<RawEvents, Error> {
+    pub fn events_no_sync(&mut self) -> Result<RawEvents, DeviceError> {
         try!(self.fill_events());
         Ok(RawEvents::new(self))
     }
@@ -953,29 +1546,138 @@ impl Device {
     /// Exposes the raw evdev events, doing synchronization on SYN_DROPPED.
     ///
     /// Will insert "fake" events
-    pub fn events(&mut self) -> Result<RawEvents, Error> {
+    pub fn events(&mut self) -> Result<RawEvents, DeviceError> {
         try!(self.fill_events());
         try!(self.compensate_dropped());
 
-        Ok(RawEvents(self))
+        Ok(RawEvents::new(self))
+    }
+
+    /// Like `events`, but yields typed `InputEvent`s.
+    pub fn typed_events(&mut self) -> Result<Events<'_>, DeviceError> {
+        Ok(Events(self.events()?))
+    }
+
+    /// Like `events`, but yields whole frames: the events up to and including a `SYN_REPORT`,
+    /// which the kernel means to be applied all at once.
+    ///
+    /// A frame that hasn't been completed yet is held back until its `SYN_REPORT` has been read,
+    /// so it comes out whole from a later call (or from `events`).
+    pub fn frames(&mut self) -> Result<Frames<'_>, DeviceError> {
+        self.fill_events()?;
+        self.compensate_dropped()?;
+        self.last_seen = self.pending_events.len();
+        Ok(Frames {
+            clock: self.clock,
+            events: &self.pending_events,
+            handed_out: &mut self.handed_out,
+            frame_start: &mut self.frame_start,
+            state: &mut self.state,
+            ff_effect_status: &mut self.ff_effect_status,
+        })
     }
 }
 
-pub struct Events<'a>(&'a mut Device);
+fn is_syn_report(ev: &input_event) -> bool {
+    ev._type == SYNCHRONIZATION.number::<u16>() && ev.code == SYN_REPORT as u16
+}
 
-pub struct RawEvents<'a>(&'a mut Device);
+/// Updates what a `Device` keeps track of with a frame the application has been handed, unless
+/// the state is newer than the frame (as it is after a sync). `frame` ends with its `SYN_REPORT`.
+fn apply_frame(state: &mut DeviceState, ff_effect_status: &mut HashMap<i16, FFStatus>, frame: &[input_event]) {
+    let time = frame[frame.len() - 1].time;
+    if (time.tv_sec, time.tv_usec) < (state.timestamp.tv_sec, state.timestamp.tv_usec) {
+        return;
+    }
+    state.timestamp = time;
+    for ev in frame {
+        let code = ev.code as usize;
+        if ev._type == KEY.number::<u16>() {
+            if code < state.key_vals.len() {
+                state.key_vals.set(code, ev.value != 0);
+            }
+        } else if ev._type == ABSOLUTE.number::<u16>() {
+            if let Some(info) = state.abs_vals.get_mut(code) {
+                info.value = ev.value;
+            }
+            if code != ABS_MT_SLOT.number::<usize>() && !state.mt_vals.is_empty() {
+                if let Some(idx) = mt_index(ev.code) {
+                    let slot = state.abs_vals[ABS_MT_SLOT.number::<usize>()].value;
+                    if let Some(vals) = state.mt_vals.get_mut(slot as usize) {
+                        vals[idx] = ev.value;
+                    }
+                }
+            }
+        } else if ev._type == SWITCH.number::<u16>() {
+            if code < state.switch_vals.len() {
+                state.switch_vals.set(code, ev.value != 0);
+            }
+        } else if ev._type == LED.number::<u16>() {
+            if code < state.led_vals.len() {
+                state.led_vals.set(code, ev.value != 0);
+            }
+        } else if ev._type == FORCEFEEDBACKSTATUS.number::<u16>() {
+            if let Some(status) = 1u32.checked_shl(ev.value as u32).and_then(FFStatus::from_bits) {
+                ff_effect_status.insert(ev.code as i16, status);
+            }
+        }
+    }
+}
 
-impl<'a> RawEvents<'a> {
-    fn new(dev: &'a mut Device) -> RawEvents<'a> {
-        dev.pending_events.reverse();
-        RawEvents(dev)
+/// An exclusive grab of a `Device`, returned by `Device::grab`. The grab is released when this is
+/// dropped, including while unwinding from a panic.
+///
+/// The device is still usable through the guard.
+#[derive(Debug)]
+pub struct GrabGuard<'a>(&'a mut Device);
+
+impl<'a> GrabGuard<'a> {
+    /// Releases the grab, reporting any error (which dropping the guard would ignore).
+    pub fn ungrab(self) -> Result<(), Error> {
+        let fd = self.0.fd;
+        std::mem::forget(self);
+        do_ioctl!(eviocgrab(fd, 0));
+        Ok(())
+    }
+}
+
+impl<'a> std::ops::Deref for GrabGuard<'a> {
+    type Target = Device;
+
+    fn deref(&self) -> &Device {
+        self.0
+    }
+}
+
+impl<'a> std::ops::DerefMut for GrabGuard<'a> {
+    fn deref_mut(&mut self) -> &mut Device {
+        self.0
     }
 }
 
-impl<'a> Drop for RawEvents<'a> {
+impl<'a> Drop for GrabGuard<'a> {
     fn drop(&mut self) {
-        self.0.pending_events.reverse();
-        self.0.last_seen = self.0.pending_events.len();
+        unsafe { let _ = eviocgrab(self.0.fd, 0); }
+    }
+}
+
+/// Typed events, returned by `Device::typed_events`.
+pub struct Events<'a>(RawEvents<'a>);
+
+impl<'a> Iterator for Events<'a> {
+    type Item = event::InputEvent;
+
+    fn next(&mut self) -> Option<event::InputEvent> {
+        self.0.next().map(event::InputEvent::from)
+    }
+}
+
+pub struct RawEvents<'a>(&'a mut Device);
+
+impl<'a> RawEvents<'a> {
+    fn new(dev: &'a mut Device) -> RawEvents<'a> {
+        dev.last_seen = dev.pending_events.len();
+        RawEvents(dev)
     }
 }
 
@@ -984,7 +1686,192 @@ impl<'a> Iterator for RawEvents<'a> {
 
     #[inline(always)]
     fn next(&mut self) -> Option<raw::input_event> {
-        self.0.pending_events.pop()
+        let dev = &mut *self.0;
+        let ev = *dev.pending_events.get(dev.handed_out)?;
+        dev.handed_out += 1;
+        if is_syn_report(&ev) {
+            apply_frame(&mut dev.state, &mut dev.ff_effect_status, &dev.pending_events[dev.frame_start..dev.handed_out]);
+            dev.frame_start = dev.handed_out;
+        }
+        Some(ev)
+    }
+}
+
+/// The events of one frame, ending with its `SYN_REPORT`.
+#[derive(Copy, Clone, Debug)]
+pub struct Frame<'a> {
+    /// The time of the `SYN_REPORT`.
+    pub timestamp: EventTime,
+    pub events: &'a [input_event],
+}
+
+/// Complete frames, returned by `Device::frames`. The events are borrowed from the `Device`, and
+/// dropped from it on the next read.
+pub struct Frames<'a> {
+    clock: Clock,
+    events: &'a [input_event],
+    handed_out: &'a mut usize,
+    frame_start: &'a mut usize,
+    state: &'a mut DeviceState,
+    ff_effect_status: &'a mut HashMap<i16, FFStatus>,
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = Frame<'a>;
+
+    fn next(&mut self) -> Option<Frame<'a>> {
+        let start = *self.handed_out;
+        let end = start + self.events[start..].iter().position(is_syn_report)?;
+        apply_frame(self.state, self.ff_effect_status, &self.events[*self.frame_start..=end]);
+        *self.handed_out = end + 1;
+        *self.frame_start = end + 1;
+        Some(Frame {
+            timestamp: EventTime::new(self.clock, self.events[end].time),
+            events: &self.events[start..=end],
+        })
+    }
+}
+
+/// Keeps the Num Lock, Caps Lock and Scroll Lock LEDs of several keyboards consistent.
+///
+/// Lock state is per keyboard as far as the kernel is concerned, so with more than one keyboard
+/// attached the LEDs drift apart as soon as a lock key is pressed. Call `sync` whenever one of the
+/// keyboards may have changed its LEDs (for example, after reading a batch of events from it).
+#[derive(Debug)]
+pub struct LockLedMirror {
+    state: Led,
+}
+
+impl LockLedMirror {
+    pub fn new() -> LockLedMirror {
+        LockLedMirror { state: Led::empty() }
+    }
+
+    /// The lock LEDs that were lit on every keyboard after the last `sync`.
+    pub fn state(&self) -> Led {
+        self.state
+    }
+
+    /// Finds a device whose lock LEDs changed since the last call, and turns the LEDs of all the
+    /// others to match. If several devices changed, the first one in `devices` wins.
+    pub fn sync(&mut self, devices: &mut [&mut Device]) -> Result<(), Error> {
+        let locks = LED_NUML | LED_CAPSL | LED_SCROLLL;
+        for dev in devices.iter_mut() {
+            dev.sync_leds()?;
+        }
+        let keyboards: Vec<_> = devices.iter().map(|dev| (dev.led, dev.lit_leds())).collect();
+        self.follow(&keyboards);
+        for dev in devices.iter_mut() {
+            if (dev.lit_leds() ^ self.state) & dev.led & locks != Led::empty() {
+                dev.update_leds(self.state, locks)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes the lock LEDs of the first keyboard whose lit LEDs differ from `state`, given each
+    /// keyboard's LEDs and which of them are lit.
+    fn follow(&mut self, keyboards: &[(Led, Led)]) {
+        let locks = LED_NUML | LED_CAPSL | LED_SCROLLL;
+        // Only compare the LEDs each keyboard actually has; a keyboard without a Scroll Lock LED
+        // would otherwise always look like it changed.
+        if let Some(&(leds, lit)) = keyboards.iter().find(|&&(leds, lit)| (lit ^ self.state) & leds & locks != Led::empty()) {
+            let changed = leds & locks;
+            self.state = (self.state - changed) | (lit & changed);
+        }
+    }
+}
+
+impl Default for LockLedMirror {
+    fn default() -> LockLedMirror {
+        LockLedMirror::new()
+    }
+}
+
+/// Works out the actual range of absolute axes from the values they report, for devices (worn
+/// joysticks, badly configured tablets) whose advertised range is off.
+///
+/// Have the user move the device through its full range, passing every event to `record`, then
+/// `apply` the ranges that were seen.
+#[derive(Clone, Debug)]
+pub struct AbsCalibration {
+    axes: AbsoluteAxis,
+    // The lowest and highest value seen on each axis, by code.
+    ranges: Vec<Option<(i32, i32)>>,
+}
+
+impl AbsCalibration {
+    /// Starts calibrating `axes`. Events for any other axis are ignored.
+    pub fn new(axes: AbsoluteAxis) -> AbsCalibration {
+        AbsCalibration { axes, ranges: vec![None; 0x40] }
+    }
+
+    pub fn record(&mut self, ev: &input_event) {
+        if ev._type != ABSOLUTE.number::<u16>() || ev.code >= 0x40 || self.axes.bits() & 1 << ev.code == 0 {
+            return;
+        }
+        let range = &mut self.ranges[ev.code as usize];
+        *range = Some(match *range {
+            Some((min, max)) => (std::cmp::min(min, ev.value), std::cmp::max(max, ev.value)),
+            None => (ev.value, ev.value),
+        });
+    }
+
+    /// The lowest and highest values seen on `axis` so far, if it has reported any. `None` if
+    /// `axis` isn't exactly one axis.
+    pub fn observed(&self, axis: AbsoluteAxis) -> Option<(i32, i32)> {
+        if axis.bits().count_ones() != 1 {
+            return None;
+        }
+        self.ranges[axis.number::<usize>()]
+    }
+
+    /// Sets the range of each calibrated axis of `dev` that reported at least two different values
+    /// to the values seen, leaving everything else about the axis as it was.
+    pub fn apply(&self, dev: &mut Device) -> Result<(), Error> {
+        for (code, range) in self.ranges.iter().enumerate() {
+            let (min, max) = match *range {
+                Some((min, max)) if min < max => (min, max),
+                _ => continue,
+            };
+            let axis = AbsoluteAxis::from_bits_truncate(1 << code);
+            if axis.is_empty() || !dev.abs.contains(axis) {
+                continue;
+            }
+            let mut info = input_absinfo::default();
+            do_ioctl!(eviocgabs(dev.fd, code as u32, &mut info));
+            info.minimum = min;
+            info.maximum = max;
+            dev.set_abs_info(axis, info)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes `events` to `fd` in a single `write(2)`, terminating them with a `SYN_REPORT` if they
+/// aren't already, so that the kernel sees them as one complete packet.
+fn write_frame(fd: RawFd, events: &[input_event]) -> Result<(), Error> {
+    let mut frame = events.to_vec();
+    let terminated = match events.last() {
+        Some(ev) => ev._type == SYNCHRONIZATION.number::<u16>() && ev.code == SYN_REPORT as u16,
+        None => return Ok(()),
+    };
+    if !terminated {
+        frame.push(input_event {
+            _type: SYNCHRONIZATION.number(),
+            code: SYN_REPORT as u16,
+            ..input_event::default()
+        });
+    }
+    let len = size_of::<input_event>() * frame.len();
+    let sz = unsafe { libc::write(fd, frame.as_ptr() as *const libc::c_void, len as libc::size_t) };
+    if sz == -1 {
+        Err(Error::from_errno(::nix::Errno::last()))
+    } else if sz as usize != len {
+        // evdev and uinput consume whole events, so this means the kernel stopped part-way.
+        Err(Error::Sys(::nix::Errno::EIO))
+    } else {
+        Ok(())
     }
 }
 
@@ -0,0 +1,129 @@
+//! Names of event types, codes and values, as spelled in the kernel headers.
+//!
+//! This is the equivalent of libevdev's `libevdev_event_type_get_name` family: it turns the
+//! numbers in a `raw::input_event` into names such as `"EV_ABS"` and `"ABS_MT_POSITION_X"`, and
+//! back. Lookups by name accept the aliases the headers define (e.g. `"SW_RADIO"`), while lookups
+//! by number give the primary name. Key codes are named by `Key::name`.
+
+use Key;
+
+/// The name of event type `ty`, e.g. `"EV_KEY"`.
+pub fn type_name(ty: u16) -> Option<&'static str> {
+    name(EV_NAMES, ty)
+}
+
+pub fn type_from_name(name: &str) -> Option<u16> {
+    code(EV_NAMES, name)
+}
+
+/// The codes of events of type `ty`, unless they are keys or don't have names.
+fn code_table(ty: u16) -> Option<&'static [(u16, &'static str)]> {
+    match ty {
+        0x00 => Some(SYN_NAMES),
+        0x02 => Some(REL_NAMES),
+        0x03 => Some(ABS_NAMES),
+        0x04 => Some(MSC_NAMES),
+        0x05 => Some(SW_NAMES),
+        0x11 => Some(LED_NAMES),
+        0x12 => Some(SND_NAMES),
+        0x14 => Some(REP_NAMES),
+        0x15 => Some(FF_NAMES),
+        _ => None,
+    }
+}
+
+/// The name of `code` for events of type `ty`, e.g. `"ABS_MT_POSITION_X"` for `(3, 0x35)`.
+pub fn code_name(ty: u16, code: u16) -> Option<&'static str> {
+    if ty == 0x01 {
+        return Key::from_code(code).map(|key| key.name());
+    }
+    name(code_table(ty)?, code)
+}
+
+pub fn code_from_name(ty: u16, name: &str) -> Option<u16> {
+    if ty == 0x01 {
+        return name.parse::<Key>().ok().map(|key| key.code());
+    }
+    code(code_table(ty)?, name)
+}
+
+/// The event type and code a code name belongs to, e.g. `(3, 0x35)` for `"ABS_MT_POSITION_X"`.
+pub fn type_and_code_from_name(name: &str) -> Option<(u16, u16)> {
+    EV_NAMES.iter().filter_map(|&(ty, _)| Some((ty, code_from_name(ty, name)?))).next()
+}
+
+/// The table of names for the values of events with type `ty` and code `code`, for the few that
+/// have any.
+fn value_table(ty: u16, code: u16) -> Option<&'static [(u16, &'static str)]> {
+    match (ty, code) {
+        (0x03, 0x37) => Some(MT_TOOL_NAMES),
+        (0x17, _) => Some(FF_STATUS_NAMES),
+        _ => None,
+    }
+}
+
+/// The name of `value` in an event with type `ty` and code `code`. Only the values of
+/// `ABS_MT_TOOL_TYPE` (e.g. `"MT_TOOL_PEN"`) and `EV_FF_STATUS` have names.
+pub fn value_name(ty: u16, code: u16, value: i32) -> Option<&'static str> {
+    if value < 0 || value > u16::MAX as i32 {
+        return None;
+    }
+    name(value_table(ty, code)?, value as u16)
+}
+
+pub fn value_from_name(ty: u16, code: u16, name: &str) -> Option<i32> {
+    self::code(value_table(ty, code)?, name).map(|value| value as i32)
+}
+
+/// The name of device property `prop`, e.g. `"INPUT_PROP_DIRECT"`.
+pub fn property_name(prop: u16) -> Option<&'static str> {
+    name(INPUT_PROP_NAMES, prop)
+}
+
+pub fn property_from_name(name: &str) -> Option<u16> {
+    code(INPUT_PROP_NAMES, name)
+}
+
+fn name(table: &[(u16, &'static str)], code: u16) -> Option<&'static str> {
+    table.iter().find(|&&(c, _)| c == code).map(|&(_, name)| name)
+}
+
+fn code(table: &[(u16, &'static str)], name: &str) -> Option<u16> {
+    table.iter().find(|&&(_, n)| n == name).map(|&(code, _)| code)
+}
+
+include!("code_names.rs");
+
+// The codes and values defined in linux/input.h rather than input-event-codes.h.
+
+const FF_NAMES: &[(u16, &str)] = &[
+    (0x50, "FF_RUMBLE"),
+    (0x51, "FF_PERIODIC"),
+    (0x52, "FF_CONSTANT"),
+    (0x53, "FF_SPRING"),
+    (0x54, "FF_FRICTION"),
+    (0x55, "FF_DAMPER"),
+    (0x56, "FF_INERTIA"),
+    (0x57, "FF_RAMP"),
+    (0x58, "FF_SQUARE"),
+    (0x59, "FF_TRIANGLE"),
+    (0x5a, "FF_SINE"),
+    (0x5b, "FF_SAW_UP"),
+    (0x5c, "FF_SAW_DOWN"),
+    (0x5d, "FF_CUSTOM"),
+    (0x60, "FF_GAIN"),
+    (0x61, "FF_AUTOCENTER"),
+    (0x60, "FF_MAX_EFFECTS"),
+];
+
+const MT_TOOL_NAMES: &[(u16, &str)] = &[
+    (0x00, "MT_TOOL_FINGER"),
+    (0x01, "MT_TOOL_PEN"),
+    (0x02, "MT_TOOL_PALM"),
+    (0x0a, "MT_TOOL_DIAL"),
+];
+
+const FF_STATUS_NAMES: &[(u16, &str)] = &[
+    (0x00, "FF_STATUS_STOPPED"),
+    (0x01, "FF_STATUS_PLAYING"),
+];
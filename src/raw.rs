@@ -4,10 +4,10 @@ ioctl!(read eviocgkeycode with b'E', 0x04; [::libc::c_uint; 2]);
 ioctl!(read eviocgrep with b'E', 0x03; [::libc::c_uint; 2]);
 ioctl!(read eviocgversion with b'E', 0x01; ::libc::c_int);
 ioctl!(write_int eviocrmff with b'E', 0x81);
-// ioctl!(read eviocgkeycode_v2 with b'E', 0x04; /*struct*/ input_keymap_entry);
+ioctl!(read eviocgkeycode_v2 with b'E', 0x04; /*struct*/ input_keymap_entry);
 // TODO #define EVIOCSFF _IOC ( _IOC_WRITE , 'E' , 0x80 , sizeof ( struct ff_effect ) )
 ioctl!(write_ptr eviocskeycode with b'E', 0x04; [::libc::c_uint; 2]);
-// ioctl!(write_int eviocskeycode_v2 with b'E', 0x04; /*struct*/ input_keymap_entry);
+ioctl!(write_ptr eviocskeycode_v2 with b'E', 0x04; /*struct*/ input_keymap_entry);
 ioctl!(write_ptr eviocsrep with b'E', 0x03; [::libc::c_uint; 2]);
 
 #[repr(C)]
@@ -21,10 +21,22 @@ pub struct input_event {
 impl ::std::default::Default for input_event {
     fn default() -> Self { unsafe { ::std::mem::zeroed() } }
 }
+/// Shows the type, code and value by name where they have one (see the `names` module).
 impl ::std::fmt::Debug for input_event {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
-        write!(f, "input_event {{ time: {{ tv_sec: {}, tv_usec: {} }}, _type: {}, code: {}, value: {}",
-               self.time.tv_sec, self.time.tv_usec, self._type, self.code, self.value)
+        write!(f, "input_event {{ time: {{ tv_sec: {}, tv_usec: {} }}, _type: ", self.time.tv_sec, self.time.tv_usec)?;
+        match ::names::type_name(self._type) {
+            Some(name) => write!(f, "{}", name)?,
+            None => write!(f, "{}", self._type)?,
+        }
+        match ::names::code_name(self._type, self.code) {
+            Some(name) => write!(f, ", code: {}", name)?,
+            None => write!(f, ", code: {}", self.code)?,
+        }
+        match ::names::value_name(self._type, self.code, self.value) {
+            Some(name) => write!(f, ", value: {} }}", name),
+            None => write!(f, ", value: {} }}", self.value),
+        }
     }
 }
 
@@ -105,6 +117,7 @@ pub struct input_keymap_entry {
 impl ::std::default::Default for input_keymap_entry {
     fn default() -> Self { unsafe { ::std::mem::zeroed() } }
 }
+pub const INPUT_KEYMAP_BY_INDEX: u8 = 1 << 0;
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct ff_replay {
@@ -204,7 +217,7 @@ ioctl!(read_buf eviocgsw with b'E', 0x1b; u8);
 ioctl!(write_ptr eviocsff with b'E', 0x80; ff_effect);
 ioctl!(write_int eviocgrab with b'E', 0x90);
 ioctl!(write_int eviocrevoke with b'E', 0x91);
-ioctl!(write_int eviocsclockid with b'E', 0xa0);
+ioctl!(write_ptr eviocsclockid with b'E', 0xa0; ::libc::c_int);
 
 pub unsafe fn eviocgbit(fd: ::libc::c_int, ev: u32, len: ::libc::c_int, buf: *mut u8) -> ::nix::Result<i32> {
     convert_ioctl_res!(::nix::libc::ioctl(fd, ior!(b'E', 0x20 + ev, len) as ::libc::c_ulong, buf))
@@ -214,3 +227,52 @@ pub unsafe fn eviocgabs(fd: ::libc::c_int, abs: u32, buf: *mut input_absinfo) ->
     convert_ioctl_res!(::nix::libc::ioctl(fd, ior!(b'E', 0x40 + abs, ::std::mem::size_of::<input_absinfo>()) as ::libc::c_ulong, buf))
 }
 
+/// Sets the parameters of the absolute axis `abs` of the device behind `fd`.
+///
+/// # Safety
+///
+/// `buf` must point to a valid `input_absinfo`, which the kernel reads during the call. `fd` is
+/// only passed on to `ioctl`, but if it isn't an evdev device the request may mean something else
+/// to its driver.
+pub unsafe fn eviocsabs(fd: ::libc::c_int, abs: u32, buf: *const input_absinfo) -> ::nix::Result<i32> {
+    convert_ioctl_res!(::nix::libc::ioctl(fd, iow!(b'E', 0xc0 + abs, ::std::mem::size_of::<input_absinfo>()) as ::libc::c_ulong, buf))
+}
+
+
+pub const UINPUT_MAX_NAME_SIZE: usize = 80;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct uinput_setup {
+    pub id: input_id,
+    pub name: [u8; UINPUT_MAX_NAME_SIZE],
+    pub ff_effects_max: u32,
+}
+impl ::std::default::Default for uinput_setup {
+    fn default() -> Self { unsafe { ::std::mem::zeroed() } }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct uinput_abs_setup {
+    pub code: u16,
+    pub absinfo: input_absinfo,
+}
+impl ::std::default::Default for uinput_abs_setup {
+    fn default() -> Self { unsafe { ::std::mem::zeroed() } }
+}
+
+ioctl!(none ui_dev_create with b'U', 1);
+ioctl!(none ui_dev_destroy with b'U', 2);
+ioctl!(write_ptr ui_dev_setup with b'U', 3; uinput_setup);
+ioctl!(write_ptr ui_abs_setup with b'U', 4; uinput_abs_setup);
+ioctl!(read_buf ui_get_sysname with b'U', 44; u8);
+ioctl!(write_int ui_set_evbit with b'U', 100);
+ioctl!(write_int ui_set_keybit with b'U', 101);
+ioctl!(write_int ui_set_relbit with b'U', 102);
+ioctl!(write_int ui_set_absbit with b'U', 103);
+ioctl!(write_int ui_set_mscbit with b'U', 104);
+ioctl!(write_int ui_set_ledbit with b'U', 105);
+ioctl!(write_int ui_set_sndbit with b'U', 106);
+ioctl!(write_int ui_set_swbit with b'U', 109);
+ioctl!(write_int ui_set_propbit with b'U', 110);
@@ -1,2 +1,846 @@
 // woo tests! should really test compensate_dropped... I don't even know how it's *supposed* to
 // behave yet though.
+
+use super::*;
+
+/// A `Device` with no capabilities, reading from an empty pipe. The write end is returned too,
+/// since reads would hit EOF rather than `EAGAIN` once it's closed.
+fn pipe_device() -> (Device, RawFd) {
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) }, 0);
+    (Device::new(fds[0]), fds[1])
+}
+
+#[test]
+fn revoke_fails_on_non_evdev_fd() {
+    let (mut dev, write_fd) = pipe_device();
+    assert!(dev.revoke().is_err());
+    assert!(!dev.is_revoked());
+    assert!(dev.events().is_ok());
+    unsafe { libc::close(write_fd); }
+}
+
+/// A `pipe_device` marked as revoked, as `Device::revoke` leaves it.
+fn revoked_pipe_device() -> (Device, RawFd) {
+    let (mut dev, write_fd) = pipe_device();
+    dev.revoked = true;
+    (dev, write_fd)
+}
+
+#[test]
+fn revoked_device_refuses_events() {
+    let (mut dev, write_fd) = revoked_pipe_device();
+    assert_eq!(dev.events().err(), Some(DeviceError::Revoked));
+    assert_eq!(dev.events_no_sync().err(), Some(DeviceError::Revoked));
+    assert_eq!(dev.frames().err(), Some(DeviceError::Revoked));
+    unsafe { libc::close(write_fd); }
+}
+
+#[test]
+fn revoked_device_refuses_sync_state() {
+    let (mut dev, write_fd) = revoked_pipe_device();
+    assert_eq!(dev.sync_state(), Err(DeviceError::Revoked));
+    unsafe { libc::close(write_fd); }
+}
+
+#[test]
+fn event_time_arithmetic() {
+    let tv = |tv_sec, tv_usec| libc::timeval { tv_sec, tv_usec };
+    let earlier = EventTime::new(Clock::Monotonic, tv(10, 900_000));
+    let later = EventTime::new(Clock::Monotonic, tv(12, 100_000));
+    assert_eq!(later.duration_since(earlier), Some(Duration::from_millis(1200)));
+    assert_eq!(earlier.duration_since(later), None);
+    assert_eq!(later.duration_since(EventTime::new(Clock::Realtime, tv(10, 900_000))), None);
+    assert_eq!(later.to_timeval().tv_usec, 100_000);
+    // Out of range fields are normalized rather than wrapped.
+    assert_eq!(EventTime::new(Clock::Monotonic, tv(10, 1_500_000)).as_duration(), Duration::from_millis(11_500));
+    assert_eq!(EventTime::new(Clock::Monotonic, tv(10, -500_000)).as_duration(), Duration::from_millis(9_500));
+    assert_eq!(EventTime::new(Clock::Monotonic, tv(-1, 0)).as_duration(), Duration::default());
+    assert_eq!(EventTime::new(Clock::Monotonic, tv(0, -1)).as_duration(), Duration::default());
+}
+
+#[test]
+fn scancode_conversions() {
+    use keymap::Scancode;
+    let code = Scancode::from(0x7002c);
+    assert_eq!(code.as_bytes().len(), 4);
+    assert_eq!(code.to_u32(), Some(0x7002c));
+    assert_eq!(code.to_string(), "7002c");
+    let odd = Scancode::from_bytes(&[0xde, 0xad, 0x01]).unwrap();
+    assert_eq!(odd.to_u32(), None);
+    assert_eq!(odd.to_string(), "dead01");
+    assert!(Scancode::from_bytes(&[]).is_none());
+    assert!(Scancode::from_bytes(&[0; 33]).is_none());
+    assert_eq!(Key::from_code(30), Some(KEY_A));
+    assert_eq!(Key::from_code(0x2fe), None);
+}
+
+#[test]
+fn hwdb_parse_and_match() {
+    use hwdb::Hwdb;
+    use keymap::Scancode;
+    let hwdb = Hwdb::parse("\
+# Fixes for the Frobnicator 3000
+evdev:input:b0003v046DpC52B*
+evdev:input:b0005v046DpC52B*
+ KEYBOARD_KEY_70039=leftctrl
+ KEYBOARD_KEY_90001=btn_left
+ ID_INPUT_KEYBOARD=1
+
+evdev:name:Frobnicator:*
+ KEYBOARD_KEY_1=esc
+").unwrap();
+    assert_eq!(hwdb.entries.len(), 2);
+    assert_eq!(hwdb.entries[0].patterns.len(), 2);
+    assert_eq!(hwdb.entries[0].keys, vec![(Scancode::from(0x70039), KEY_LEFTCTRL),
+                                          (Scancode::from(0x90001), BTN_LEFT)]);
+    let id = |bustype, product| raw::input_id { bustype, vendor: 0x46d, product, version: 0x111 };
+    assert!(hwdb.entries[0].matches(&id(5, 0xc52b)));
+    assert!(!hwdb.entries[0].matches(&id(3, 0xc52c)));
+    assert!(!hwdb.entries[1].matches(&id(5, 0xc52b)));
+
+    assert_eq!(Hwdb::parse("evdev:input:*\n KEYBOARD_KEY_1=frobnicate\n").unwrap_err().line, 2);
+    assert_eq!(Hwdb::parse("\n KEYBOARD_KEY_1=esc\n").unwrap_err().line, 2);
+}
+
+/// An event with a zero timestamp.
+fn ev(_type: u16, code: u16, value: i32) -> raw::input_event {
+    raw::input_event { _type, code, value, ..Default::default() }
+}
+
+fn write_events(fd: RawFd, events: &[(u16, u16, i32)]) {
+    write_events_at(fd, libc::timeval { tv_sec: 0, tv_usec: 0 }, events);
+}
+
+fn write_events_at(fd: RawFd, time: libc::timeval, events: &[(u16, u16, i32)]) {
+    let events: Vec<_> = events.iter().map(|&(_type, code, value)| {
+        raw::input_event { time, ..ev(_type, code, value) }
+    }).collect();
+    let len = std::mem::size_of_val(&events[..]);
+    assert_eq!(unsafe { libc::write(fd, events.as_ptr() as *const libc::c_void, len) }, len as isize);
+}
+
+/// A `Device` with no capabilities, writing into a pipe. The read end is returned too, for
+/// `read_written`.
+fn pipe_writer_device() -> (Device, RawFd) {
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) }, 0);
+    (Device::new(fds[1]), fds[0])
+}
+
+/// The events written into the pipe behind `fd` so far, as `(type, code, value)`.
+fn read_written(fd: RawFd) -> Vec<(u16, u16, i32)> {
+    let mut events = vec![raw::input_event::default(); 64];
+    let len = std::mem::size_of_val(&events[..]);
+    let sz = unsafe { libc::read(fd, events.as_mut_ptr() as *mut libc::c_void, len) };
+    events.truncate(sz.max(0) as usize / std::mem::size_of::<raw::input_event>());
+    events.iter().map(|ev| (ev._type, ev.code, ev.value)).collect()
+}
+
+#[test]
+fn mt_slots_follow_events() {
+    let (mut dev, write_fd) = pipe_device();
+    dev.ty = ABSOLUTE | SYNCHRONIZATION;
+    dev.abs = ABS_MT_SLOT | ABS_MT_POSITION_X | ABS_MT_TRACKING_ID;
+    dev.state.abs_vals = vec![raw::input_absinfo::default(); 0x3f];
+    dev.state.mt_vals = vec![[-1; MT_AXES]; 2];
+    write_events(write_fd, &[(3, 0x39, 7), (3, 0x35, 100), (3, 0x2f, 1), (3, 0x39, 8), (3, 0x35, 200),
+                             (0, SYN_REPORT as u16, 0)]);
+    // An ABS event must not be mistaken for SYN_DROPPED, which would make this resync and fail.
+    assert_eq!(dev.events().unwrap().count(), 6);
+    assert_eq!(dev.state().mt_value(0, ABS_MT_TRACKING_ID), Some(7));
+    assert_eq!(dev.state().mt_value(0, ABS_MT_POSITION_X), Some(100));
+    assert_eq!(dev.state().mt_value(1, ABS_MT_POSITION_X), Some(200));
+    assert_eq!(dev.state().mt_value(2, ABS_MT_POSITION_X), None);
+    unsafe { libc::close(write_fd); }
+}
+
+/// Reads the events from `dev` with `Device::events`, as `(type, code, value)`.
+fn read_events(dev: &mut Device) -> Vec<(u16, u16, i32)> {
+    dev.events().unwrap().map(|ev| (ev._type, ev.code, ev.value)).collect()
+}
+
+#[test]
+fn events_come_in_order() {
+    let (mut dev, write_fd) = pipe_device();
+    dev.ty = SYNCHRONIZATION;
+    let report = (0, SYN_REPORT as u16, 0);
+    write_events(write_fd, &[(1, 30, 1), (1, 31, 1), report]);
+    assert_eq!(read_events(&mut dev), vec![(1, 30, 1), (1, 31, 1), report]);
+    unsafe { libc::close(write_fd); }
+}
+
+#[test]
+fn only_syn_events_are_syn_dropped() {
+    let (mut dev, write_fd) = pipe_device();
+    dev.ty = SYNCHRONIZATION;
+    let report = (0, SYN_REPORT as u16, 0);
+    // EV_ABS has the same number as SYN_DROPPED.
+    write_events(write_fd, &[(3, SYN_DROPPED as u16, 5), report]);
+    assert_eq!(read_events(&mut dev), vec![(3, SYN_DROPPED as u16, 5), report]);
+    unsafe { libc::close(write_fd); }
+}
+
+#[test]
+fn syn_dropped_keeps_the_report_before_it() {
+    let (mut dev, write_fd) = pipe_device();
+    dev.ty = SYNCHRONIZATION;
+    let report = (0, SYN_REPORT as u16, 0);
+    write_events(write_fd, &[(1, 30, 1), report, (0, SYN_DROPPED as u16, 0), (1, 31, 1), report]);
+    // The frame before the SYN_DROPPED is whole, and the resync adds a frame of its own (empty,
+    // since the device has no state).
+    assert_eq!(read_events(&mut dev), vec![(1, 30, 1), report, report]);
+    unsafe { libc::close(write_fd); }
+}
+
+#[test]
+fn syn_dropped_after_events_already_seen() {
+    let (mut dev, write_fd) = pipe_device();
+    dev.ty = SYNCHRONIZATION;
+    let report = (0, SYN_REPORT as u16, 0);
+    write_events(write_fd, &[(1, 30, 1)]);
+    assert_eq!(read_events(&mut dev), vec![(1, 30, 1)]);
+    // The SYN_DROPPED is found after the event already seen, and only what follows the
+    // SYN_REPORT right before it is dropped.
+    write_events(write_fd, &[report, (1, 31, 1), report, (0, SYN_DROPPED as u16, 0), (1, 32, 1), report]);
+    assert_eq!(read_events(&mut dev), vec![report, (1, 31, 1), report, report]);
+    unsafe { libc::close(write_fd); }
+}
+
+#[test]
+fn syn_dropped_diffs_against_the_frames_left() {
+    let (mut dev, write_fd) = pipe_device();
+    dev.ty = KEY | SYNCHRONIZATION;
+    dev.key_bits.insert(30);
+    dev.key_bits.insert(31);
+    // What's left once the events after the last SYN_REPORT before a SYN_DROPPED are thrown away;
+    // the application hasn't been handed any of it yet.
+    dev.pending_events = vec![ev(1, 30, 1), ev(0, SYN_REPORT as u16, 0), ev(1, 31, 1)];
+    let old_state = dev.state_after_pending();
+    assert!(old_state.key_vals[30]);
+    assert!(!old_state.key_vals[31]);
+    assert!(!dev.state().key_vals[30]);
+    unsafe { libc::close(write_fd); }
+}
+
+#[test]
+fn touch_tracker_phases() {
+    use touch::{TouchPhase, TouchTracker};
+    let mut tracker = TouchTracker::with_slots(2);
+    let mut feed = |events: &[(u16, u16, i32)]| {
+        for &(ty, code, value) in events {
+            assert!(tracker.process(&ev(ty, code, value)).is_none());
+        }
+        tracker.process(&ev(0, SYN_REPORT as u16, 0))
+               .unwrap()
+               .iter()
+               .map(|c| (c.slot, c.tracking_id, c.phase, c.x))
+               .collect::<Vec<_>>()
+    };
+    assert_eq!(feed(&[(3, 0x39, 10), (3, 0x35, 5)]), vec![(0, 10, TouchPhase::Began, 5)]);
+    assert_eq!(feed(&[(3, 0x2f, 1), (3, 0x39, 11), (3, 0x35, 50)]),
+               vec![(0, 10, TouchPhase::Moved, 5), (1, 11, TouchPhase::Began, 50)]);
+    // Slot 1 is still selected, and its contact is replaced within one frame.
+    assert_eq!(feed(&[(3, 0x39, -1), (3, 0x39, 12), (3, 0x35, 60)]),
+               vec![(0, 10, TouchPhase::Moved, 5), (1, 11, TouchPhase::Ended, 50),
+                    (1, 12, TouchPhase::Began, 60)]);
+    assert_eq!(feed(&[(3, 0x2f, 0), (3, 0x39, -1)]),
+               vec![(0, 10, TouchPhase::Ended, 5), (1, 12, TouchPhase::Moved, 60)]);
+    // A partial frame before SYN_DROPPED is thrown away.
+    assert!(tracker.process(&ev(3, 0x39, 13)).is_none());
+    assert!(tracker.process(&ev(0, SYN_DROPPED as u16, 0)).is_none());
+    assert!(tracker.process(&ev(0, SYN_REPORT as u16, 0)).is_none());
+    assert_eq!(tracker.contacts().len(), 1);
+}
+
+#[test]
+fn protocol_a_contacts_keep_their_slots() {
+    use touch::{ProtocolAConverter, TouchPhase, TouchTracker};
+    let mut converter = ProtocolAConverter::new(4);
+    let mut tracker = TouchTracker::with_slots(4);
+    let mut feed = |contacts: &[(i32, i32)]| {
+        let mut events = Vec::new();
+        for &(x, y) in contacts {
+            events.extend_from_slice(&[ev(3, 0x35, x), ev(3, 0x36, y), ev(0, SYN_MT_REPORT as u16, 0)]);
+        }
+        events.push(ev(1, BTN_TOUCH as u16, !contacts.is_empty() as i32));
+        events.push(ev(0, SYN_REPORT as u16, 0));
+        let mut out = Vec::new();
+        for ev in &events {
+            if let Some(converted) = converter.process(ev) {
+                out.extend_from_slice(converted);
+            }
+        }
+        assert!(out.iter().all(|ev| ev._type != 0 || ev.code != SYN_MT_REPORT as u16));
+        assert!(out.iter().any(|ev| ev._type == 1 && ev.code == BTN_TOUCH as u16));
+        let mut frame = None;
+        for ev in &out {
+            frame = tracker.process(ev).map(|contacts| {
+                contacts.iter().map(|c| (c.slot, c.tracking_id, c.phase, c.x, c.y)).collect::<Vec<_>>()
+            });
+        }
+        frame.unwrap()
+    };
+    assert_eq!(feed(&[(10, 10), (100, 100)]),
+               vec![(0, 0, TouchPhase::Began, 10, 10), (1, 1, TouchPhase::Began, 100, 100)]);
+    // Reported in the other order, but each contact is still matched to its nearest predecessor.
+    assert_eq!(feed(&[(95, 105), (12, 11)]),
+               vec![(0, 0, TouchPhase::Moved, 12, 11), (1, 1, TouchPhase::Moved, 95, 105)]);
+    assert_eq!(feed(&[(90, 110)]),
+               vec![(0, 0, TouchPhase::Ended, 12, 11), (1, 1, TouchPhase::Moved, 90, 110)]);
+    assert_eq!(feed(&[(90, 110), (500, 500)]),
+               vec![(0, 2, TouchPhase::Began, 500, 500), (1, 1, TouchPhase::Moved, 90, 110)]);
+    assert_eq!(feed(&[]),
+               vec![(0, 2, TouchPhase::Ended, 500, 500), (1, 1, TouchPhase::Ended, 90, 110)]);
+}
+
+#[test]
+fn abs_calibration_records_ranges() {
+    let mut calibration = AbsCalibration::new(ABS_X | ABS_Y);
+    for &(code, value) in &[(0, 512), (0, 30), (1, 7), (0, 990), (2, -5000)] {
+        calibration.record(&ev(3, code, value));
+    }
+    calibration.record(&ev(2, 0, -1000));
+    assert_eq!(calibration.observed(ABS_X), Some((30, 990)));
+    assert_eq!(calibration.observed(ABS_Y), Some((7, 7)));
+    assert_eq!(calibration.observed(ABS_Z), None);
+    assert_eq!(calibration.observed(AbsoluteAxis::empty()), None);
+    assert_eq!(calibration.observed(ABS_X | ABS_Y), None);
+}
+
+#[test]
+fn abs_info_needs_exactly_one_axis() {
+    let (mut dev, write_fd) = pipe_device();
+    dev.ty = ABSOLUTE;
+    dev.abs = ABS_X | ABS_Y;
+    dev.state.abs_vals = vec![raw::input_absinfo::default(); 0x3f];
+    assert!(dev.abs_info(ABS_X).is_some());
+    assert!(dev.abs_info(AbsoluteAxis::empty()).is_none());
+    assert!(dev.abs_info(ABS_X | ABS_Y).is_none());
+    let info = raw::input_absinfo::default();
+    assert_eq!(dev.set_abs_info(AbsoluteAxis::empty(), info), Err(Error::UnsupportedOperation));
+    assert_eq!(dev.set_abs_info(ABS_X | ABS_Y, info), Err(Error::UnsupportedOperation));
+    // A single axis gets as far as the ioctl, which a pipe doesn't take.
+    assert_eq!(dev.set_abs_info(ABS_X, info), Err(Error::Sys(::nix::Errno::ENOTTY)));
+    unsafe { libc::close(write_fd); }
+}
+
+#[test]
+fn abs_normalization() {
+    let stick = raw::input_absinfo { value: 0, minimum: -100, maximum: 100, fuzz: 0, flat: 10, resolution: 0 };
+    assert_eq!(stick.normalize_centered(5), 0.0);
+    assert_eq!(stick.normalize_centered(-10), 0.0);
+    assert_eq!(stick.normalize_centered(55), 0.5);
+    assert_eq!(stick.normalize_centered(-100), -1.0);
+    assert_eq!(stick.normalize_centered(-200), -1.0);
+    assert_eq!(stick.to_physical(50), None);
+
+    let trigger = raw::input_absinfo { value: 0, minimum: 0, maximum: 255, fuzz: 0, flat: 15, resolution: 0 };
+    assert_eq!(trigger.normalize(15), 0.0);
+    assert_eq!(trigger.normalize(135), 0.5);
+    assert_eq!(trigger.normalize(255), 1.0);
+
+    let mut state = DeviceState {
+        timestamp: libc::timeval { tv_sec: 0, tv_usec: 0 },
+        key_vals: FixedBitSet::with_capacity(0),
+        abs_vals: vec![raw::input_absinfo::default(); 0x3f],
+        switch_vals: FixedBitSet::with_capacity(0),
+        led_vals: FixedBitSet::with_capacity(0),
+        rep_vals: None,
+        mt_vals: vec![[0; MT_AXES]; 2],
+    };
+    state.abs_vals[0x35] = raw::input_absinfo { value: 0, minimum: 0, maximum: 4000, fuzz: 0, flat: 0, resolution: 40 };
+    state.mt_vals[1][0x35 - 0x30] = 1000;
+    assert_eq!(state.mt_normalized(1, ABS_MT_POSITION_X), Some(0.25));
+    assert_eq!(state.mt_physical(1, ABS_MT_POSITION_X), Some((25.0, PhysicalUnit::Millimeters)));
+    assert_eq!(state.mt_normalized(1, ABS_MT_POSITION_Y), None);
+    assert_eq!(state.abs_normalized(ABS_X), None);
+    assert_eq!(ABS_TILT_X.physical_unit(), Some(PhysicalUnit::Radians));
+}
+
+#[test]
+fn input_event_round_trips() {
+    use event::{InputEvent, KeyState};
+    let time = libc::timeval { tv_sec: 12, tv_usec: 34 };
+    let raw = |_type, code, value| raw::input_event { time, _type, code, value };
+    let cases = [raw(1, 30, 1), raw(1, 30, 2), raw(2, 8, -1), raw(3, 0x35, 700), raw(5, 0, 1), raw(0x11, 1, 0),
+                 raw(0x12, 2, 440), raw(4, 4, 0x7002c), raw(0, 0, 0), raw(0x17, 3, 1),
+                 // unknown key, unknown relative axis, odd key value, unknown type
+                 raw(1, 0x2fe, 1), raw(2, 0x1f, 1), raw(1, 30, 7), raw(0x1f, 0, 0)];
+    for &ev in &cases {
+        let back = raw::input_event::from(InputEvent::from(ev));
+        assert_eq!((back.time.tv_sec, back.time.tv_usec, back._type, back.code, back.value),
+                   (12, 34, ev._type, ev.code, ev.value));
+    }
+    match InputEvent::from(cases[0]) {
+        InputEvent::Key { key: KEY_A, state: KeyState::Pressed, .. } => {}
+        other => panic!("{:?}", other),
+    }
+    match InputEvent::from(cases[5]) {
+        InputEvent::Led { led, on: false, .. } => assert_eq!(led, LED_CAPSL),
+        other => panic!("{:?}", other),
+    }
+    assert!(cases[10..].iter().all(|&ev| match InputEvent::from(ev) {
+        InputEvent::Unknown(_) => true,
+        _ => false,
+    }));
+}
+
+#[test]
+fn key_names_and_codes() {
+    use std::convert::TryFrom;
+    assert_eq!(KEY_LEFTCTRL.name(), "KEY_LEFTCTRL");
+    assert_eq!(BTN_SOUTH.code(), 0x130);
+    assert_eq!("KEY_LEFTCTRL".parse(), Ok(KEY_LEFTCTRL));
+    assert_eq!("BTN_SOUTH".parse(), Ok(BTN_SOUTH));
+    assert_eq!("BTN_A".parse(), Ok(BTN_SOUTH));
+    // Aliases are at the crate root too, like the keys.
+    assert_eq!(::KEY_ZOOM, Key::KEY_FULL_SCREEN);
+    assert_eq!(::BTN_A, ::BTN_SOUTH);
+    assert_eq!("KEY_FROBNICATE".parse::<Key>(), Err(UnknownKey::Name("KEY_FROBNICATE".to_string())));
+    assert_eq!(Key::try_from(0x2fe), Err(UnknownKey::Code(0x2fe)));
+    // KEY_MAX is only the highest code.
+    assert_eq!(Key::from_code(KEY_MAX), None);
+    assert_eq!("KEY_MAX".parse::<Key>(), Err(UnknownKey::Name("KEY_MAX".to_string())));
+    for code in 0..0x300 {
+        if let Some(key) = Key::from_code(code) {
+            assert_eq!(key.code(), code);
+            assert_eq!(key.name().parse(), Ok(key));
+        }
+    }
+}
+
+#[test]
+#[allow(deprecated)]
+fn old_max_constants_are_the_highest_codes() {
+    assert_eq!(SW_MAX.number::<u16>(), 0x10);
+    assert_eq!(LED_MAX.number::<u16>(), 0x0f);
+    assert_eq!(MSC_MAX.number::<u16>(), 0x07);
+    assert!(!Led::all().contains(LED_MAX));
+    assert!(!Misc::all().contains(MSC_MAX));
+}
+
+#[test]
+fn event_code_names() {
+    use names::*;
+    assert_eq!(type_name(3), Some("EV_ABS"));
+    assert_eq!(type_from_name("EV_FF_STATUS"), Some(0x17));
+    assert_eq!(code_name(3, 0x35), Some("ABS_MT_POSITION_X"));
+    assert_eq!(code_name(1, 0x130), Some("BTN_SOUTH"));
+    assert_eq!(code_name(5, 3), Some("SW_RFKILL_ALL"));
+    assert_eq!(code_from_name(5, "SW_RADIO"), Some(3));
+    assert_eq!(code_from_name(3, "REL_X"), None);
+    assert_eq!(type_and_code_from_name("REL_WHEEL"), Some((2, 8)));
+    assert_eq!(type_and_code_from_name("BTN_A"), Some((1, 0x130)));
+    assert_eq!(type_and_code_from_name("FROB_X"), None);
+    assert_eq!(value_name(3, 0x37, 1), Some("MT_TOOL_PEN"));
+    assert_eq!(value_from_name(0x17, 0, "FF_STATUS_PLAYING"), Some(1));
+    assert_eq!(value_name(3, 0x35, 1), None);
+    assert_eq!(property_name(1), Some("INPUT_PROP_DIRECT"));
+
+    let ev = raw::input_event { time: libc::timeval { tv_sec: 1, tv_usec: 2 }, _type: 3, code: 0x37, value: 2 };
+    assert_eq!(format!("{:?}", ev),
+               "input_event { time: { tv_sec: 1, tv_usec: 2 }, _type: EV_ABS, code: ABS_MT_TOOL_TYPE, value: MT_TOOL_PALM }");
+    let ev = raw::input_event { _type: 0x1e, code: 7, value: -1, ..ev };
+    assert_eq!(format!("{:?}", ev), "input_event { time: { tv_sec: 1, tv_usec: 2 }, _type: 30, code: 7, value: -1 }");
+}
+
+#[test]
+fn generated_tables_match_header() {
+    use std::collections::HashMap;
+    use names::*;
+    // Rerun tools/gen-codes.py if this fails after updating the header.
+    let header = include_str!("../include/linux/input-event-codes.h");
+    let mut values = HashMap::new();
+    for line in header.lines() {
+        let mut words = line.split_whitespace();
+        if let (Some("#define"), Some(name), Some(value)) = (words.next(), words.next(), words.next()) {
+            values.insert(name, value);
+        }
+    }
+    let mut checked = 0;
+    for (&name, &value) in &values {
+        if name.ends_with("_MAX") || name.ends_with("_CNT") || name == "EV_VERSION" {
+            continue;
+        }
+        let mut value = value;
+        while let Some(&v) = values.get(value) {
+            value = v;
+        }
+        let code = match value.strip_prefix("0x") {
+            Some(hex) => u16::from_str_radix(hex, 16),
+            None => value.parse(),
+        };
+        let code = match code {
+            Ok(code) => code,
+            Err(_) => continue,
+        };
+        let has_flag = if name.starts_with("KEY_") || name.starts_with("BTN_") {
+            name.parse::<Key>().map(|key| key.code()) == Ok(code)
+        } else if name.starts_with("EV_") {
+            type_from_name(name) == Some(code) && Types::from_bits(1 << code).is_some()
+        } else if name.starts_with("INPUT_PROP_") {
+            property_from_name(name) == Some(code) && Props::from_bits(1 << code).is_some()
+        } else if name.starts_with("SYN_") {
+            type_and_code_from_name(name) == Some((0, code))
+        } else {
+            let (ty, has_flag) = match &name[..name.find('_').unwrap()] {
+                "REL" => (2, RelativeAxis::from_bits(1 << code).is_some()),
+                "ABS" => (3, AbsoluteAxis::from_bits(1 << code).is_some()),
+                "MSC" => (4, Misc::from_bits(1 << code).is_some()),
+                "SW" => (5, Switch::from_bits(1 << code).is_some()),
+                "LED" => (0x11, Led::from_bits(1 << code).is_some()),
+                "SND" => (0x12, Sound::from_bits(1 << code).is_some()),
+                "REP" => (0x14, Repeat::from_bits(1 << code).is_some()),
+                _ => continue,
+            };
+            has_flag && type_and_code_from_name(name) == Some((ty, code))
+        };
+        assert!(has_flag, "{} = {:#x} is missing from the generated tables", name, code);
+        checked += 1;
+    }
+    assert!(checked > 700);
+}
+
+#[test]
+fn frames_wait_for_syn_report() {
+    let (mut dev, write_fd) = pipe_device();
+    dev.ty = KEY | SYNCHRONIZATION;
+    let report = (0, SYN_REPORT as u16, 0);
+    write_events(write_fd, &[(1, 30, 1), report, (1, 30, 0), (1, 31, 1), report, (1, 32, 1)]);
+    {
+        let lens: Vec<_> = dev.frames().unwrap().map(|frame| frame.events.len()).collect();
+        assert_eq!(lens, vec![2, 3]);
+    }
+    // The incomplete frame is held back until its SYN_REPORT arrives.
+    assert_eq!(dev.frames().unwrap().count(), 0);
+    write_events_at(write_fd, libc::timeval { tv_sec: 5, tv_usec: 250_000 }, &[(1, 33, 1), report, (1, 34, 1), report]);
+    let frame = dev.frames().unwrap().next().unwrap();
+    let codes: Vec<_> = frame.events.iter().map(|ev| ev.code).collect();
+    assert_eq!(codes, vec![32, 33, SYN_REPORT as u16]);
+    assert_eq!(frame.timestamp, EventTime::new(dev.clock(), libc::timeval { tv_sec: 5, tv_usec: 250_000 }));
+    // Only the frames handed out are dropped.
+    assert_eq!(dev.events().unwrap().count(), 2);
+    unsafe { libc::close(write_fd); }
+}
+
+#[test]
+fn state_follows_completed_frames() {
+    let (mut dev, write_fd) = pipe_device();
+    dev.ty = KEY | SWITCH | SYNCHRONIZATION;
+    let report = (0, SYN_REPORT as u16, 0);
+    write_events(write_fd, &[(1, 30, 1), (5, 0, 1), report, (1, 31, 1)]);
+    assert_eq!(dev.events().unwrap().count(), 4);
+    assert!(dev.state().key_vals[30]);
+    assert!(dev.state().switch_vals[0]);
+    // KEY_S was handed out, but its frame isn't complete yet.
+    assert!(!dev.state().key_vals[31]);
+    write_events(write_fd, &[report]);
+    assert_eq!(dev.frames().unwrap().count(), 1);
+    assert!(dev.state().key_vals[31]);
+
+    // Frames from before the state's timestamp are stale.
+    dev.state.timestamp = libc::timeval { tv_sec: 1, tv_usec: 0 };
+    write_events(write_fd, &[(1, 30, 0), report]);
+    assert_eq!(dev.events().unwrap().count(), 2);
+    assert!(dev.state().key_vals[30]);
+    unsafe { libc::close(write_fd); }
+}
+
+#[test]
+fn set_clock_discards_events_from_the_old_clock() {
+    let (mut dev, write_fd) = pipe_device();
+    dev.ty = KEY | SYNCHRONIZATION;
+    let report = (0, SYN_REPORT as u16, 0);
+    write_events_at(write_fd, Clock::Realtime.now().to_timeval(), &[(1, 30, 1), report]);
+    // Read the frame in, but don't hand it out.
+    drop(dev.events_no_sync().unwrap());
+    // A pipe doesn't take EVIOCSCLOCKID, so do what `set_clock` does once the kernel has.
+    assert!(dev.set_clock(Clock::Monotonic).is_err());
+    dev.clock_switched(Clock::Monotonic);
+    write_events_at(write_fd, Clock::Monotonic.now().to_timeval(), &[(1, 31, 1), report]);
+    // The realtime frame would have put the state's timestamp far ahead of the monotonic ones.
+    assert_eq!(read_events(&mut dev), vec![(1, 31, 1), report]);
+    assert!(!dev.state().key_vals[30]);
+    assert!(dev.state().key_vals[31]);
+    unsafe { libc::close(write_fd); }
+}
+
+#[test]
+fn oversize_custom_waveform_is_refused() {
+    use ff::*;
+    let (mut dev, write_fd) = pipe_device();
+    dev.ty = FORCEFEEDBACK;
+    dev.ff.insert(FF_PERIODIC as usize);
+    dev.ff.insert(FF_CUSTOM as usize);
+    dev.ff_effects_max = 1;
+    let custom = |len| FFEffectData::new(ForceFeedbackEffect::Periodic {
+        waveform: Waveform::Custom(vec![0; len]),
+        period: 100,
+        magnitude: 0x4000,
+        offset: 0,
+        phase: 0,
+        envelope: ff_envelope::default(),
+    });
+    assert_eq!(dev.upload_ff_effect(&custom(FF_CUSTOM_MAX_SAMPLES + 1)).err(),
+               Some(DeviceError::CustomWaveformTooLong { max: FF_CUSTOM_MAX_SAMPLES }));
+    // A pipe doesn't take EVIOCSFF, so the longest allowed waveform gets as far as the kernel.
+    assert_eq!(dev.upload_ff_effect(&custom(FF_CUSTOM_MAX_SAMPLES)).err(),
+               Some(DeviceError::Nix(Error::Sys(::nix::Errno::ENOTTY))));
+    unsafe { libc::close(write_fd); }
+}
+
+#[test]
+fn virtual_device_builder_checks_before_creating() {
+    use uinput::VirtualDeviceBuilder;
+    let info = raw::input_absinfo::default();
+    // These fail before /dev/uinput is even opened.
+    let err = |builder: VirtualDeviceBuilder| builder.build().err();
+    assert_eq!(err(VirtualDeviceBuilder::new("nul\0name")), Some(Error::Sys(::nix::Errno::EINVAL)));
+    assert_eq!(err(VirtualDeviceBuilder::new(&"x".repeat(raw::UINPUT_MAX_NAME_SIZE))),
+               Some(Error::Sys(::nix::Errno::EINVAL)));
+    assert_eq!(err(VirtualDeviceBuilder::new("stick").with_absolute_axis(ABS_X | ABS_Y, info)),
+               Some(Error::UnsupportedOperation));
+    assert_eq!(err(VirtualDeviceBuilder::new("stick").with_absolute_axis(AbsoluteAxis::empty(), info)),
+               Some(Error::UnsupportedOperation));
+}
+
+#[test]
+fn written_events_are_framed() {
+    let (mut dev, read_fd) = pipe_writer_device();
+    let report = (0, SYN_REPORT as u16, 0);
+    dev.write_events(&[ev(1, 30, 1)]).unwrap();
+    // Frames that are already terminated don't get a second SYN_REPORT.
+    dev.write_events(&[ev(1, 30, 0), ev(0, SYN_REPORT as u16, 0)]).unwrap();
+    dev.write_events(&[]).unwrap();
+    dev.write_event(KEY, 31, 1).unwrap();
+    assert_eq!(read_written(read_fd), vec![(1, 30, 1), report, (1, 30, 0), report, (1, 31, 1), report]);
+    unsafe { libc::close(read_fd); }
+}
+
+#[test]
+fn set_led_only_updates_state_once_confirmed() {
+    let (mut dev, read_fd) = pipe_writer_device();
+    dev.ty = LED;
+    dev.led = LED_NUML | LED_CAPSL;
+    let report = (0, SYN_REPORT as u16, 0);
+    // The event goes out, but a pipe can't tell whether the LED came on, so the state stays.
+    assert!(dev.set_led(LED_CAPSL | LED_SCROLLL, true).is_err());
+    assert_eq!(read_written(read_fd), vec![(0x11, 1, 1), report]);
+    assert!(!dev.state().led_vals[1]);
+    assert!(dev.set_leds(LED_NUML).is_err());
+    assert_eq!(read_written(read_fd), vec![(0x11, 0, 1), (0x11, 1, 0), report]);
+    unsafe { libc::close(read_fd); }
+}
+
+#[test]
+fn lock_led_mirror_follows_changes() {
+    let all = LED_NUML | LED_CAPSL | LED_SCROLLL;
+    let no_scroll = LED_NUML | LED_CAPSL;
+    let mut mirror = LockLedMirror::new();
+    mirror.follow(&[(all, Led::empty()), (no_scroll, Led::empty())]);
+    assert_eq!(mirror.state(), Led::empty());
+    mirror.follow(&[(all, Led::empty()), (no_scroll, LED_CAPSL)]);
+    assert_eq!(mirror.state(), LED_CAPSL);
+    mirror.follow(&[(all, LED_CAPSL | LED_SCROLLL), (no_scroll, LED_CAPSL)]);
+    assert_eq!(mirror.state(), LED_CAPSL | LED_SCROLLL);
+    // The keyboard without a Scroll Lock LED doesn't count as changed.
+    mirror.follow(&[(all, LED_CAPSL | LED_SCROLLL), (no_scroll, LED_CAPSL)]);
+    assert_eq!(mirror.state(), LED_CAPSL | LED_SCROLLL);
+    // The first keyboard that changed wins.
+    mirror.follow(&[(all, LED_NUML), (no_scroll, Led::empty())]);
+    assert_eq!(mirror.state(), LED_NUML);
+    mirror.follow(&[(all, LED_NUML | LED_SCROLLL), (no_scroll, LED_NUML)]);
+    assert_eq!(mirror.state(), LED_NUML | LED_SCROLLL);
+    // Only the LEDs the winning keyboard has are taken from it, so Scroll Lock stays on.
+    mirror.follow(&[(no_scroll, LED_CAPSL), (all, LED_NUML)]);
+    assert_eq!(mirror.state(), LED_CAPSL | LED_SCROLLL);
+}
+
+#[test]
+fn sounds_are_written_as_events() {
+    let (mut dev, read_fd) = pipe_writer_device();
+    assert_eq!(dev.click(), Err(Error::UnsupportedOperation));
+    dev.ty = SOUND;
+    dev.snd = SND_CLICK | SND_BELL | SND_TONE;
+    let report = (0, SYN_REPORT as u16, 0);
+    dev.click().unwrap();
+    assert_eq!(read_written(read_fd), vec![(0x12, 0, 1), report, (0x12, 0, 0), report]);
+    dev.bell_for(Duration::from_millis(0)).unwrap();
+    assert_eq!(read_written(read_fd), vec![(0x12, 1, 1), report, (0x12, 1, 0), report]);
+    dev.tone(u32::MAX).unwrap();
+    assert_eq!(read_written(read_fd), vec![(0x12, 2, i32::MAX), report]);
+    dev.beep(440, Duration::from_millis(0)).unwrap();
+    assert_eq!(read_written(read_fd), vec![(0x12, 2, 440), report, (0x12, 2, 0), report]);
+    unsafe { libc::close(read_fd); }
+}
+
+#[test]
+fn ff_effect_handles_share_the_device_descriptor() {
+    use ff::FFEffectHandle;
+    let (dev, read_fd) = pipe_writer_device();
+    // Reading the empty pipe hits EOF rather than `EAGAIN` once the write end is closed.
+    let is_open = || unsafe { libc::read(read_fd, [0u8; 1].as_mut_ptr() as *mut libc::c_void, 1) } == -1;
+    let first = FFEffectHandle::new(&dev, 0);
+    let mut second = FFEffectHandle::new(&dev, 1);
+    assert_eq!(dev.ff_effects_in_use(), 2);
+    // Closing the descriptor would erase every effect, so neither dropping a handle nor the
+    // `Device` may do it while another handle is left.
+    drop(first);
+    assert_eq!(dev.ff_effects_in_use(), 1);
+    drop(dev);
+    assert!(is_open());
+    second.play(3).unwrap();
+    assert_eq!(read_written(read_fd), vec![(0x15, 1, 3), (0, SYN_REPORT as u16, 0)]);
+    drop(second);
+    assert!(!is_open());
+    unsafe { libc::close(read_fd); }
+}
+
+#[test]
+fn ff_effect_slots_are_counted() {
+    use ff::*;
+    let (mut dev, write_fd) = pipe_device();
+    let rumble = FFEffectData::new(ForceFeedbackEffect::Rumble { strong_magnitude: 0x8000, weak_magnitude: 0 });
+    assert_eq!(dev.upload_ff_effect(&rumble).err(), Some(DeviceError::Nix(Error::UnsupportedOperation)));
+    dev.ty = FORCEFEEDBACK;
+    dev.ff.insert(FF_RUMBLE as usize);
+    dev.ff.insert(FF_GAIN as usize);
+    assert_eq!(dev.ff_effects_supported(), vec![FF_RUMBLE, FF_GAIN]);
+    dev.ff_effects_max = 1;
+    let handle = FFEffectHandle::new(&dev, 0);
+    assert_eq!(dev.ff_effects_in_use(), 1);
+    let err = dev.upload_ff_effect(&rumble).err().unwrap();
+    assert_eq!(err, DeviceError::TooManyFFEffects { max: 1 });
+    assert_eq!(err.to_string(), "no room for another force feedback effect (the device holds 1)");
+    drop(handle);
+    assert_eq!(dev.ff_effects_in_use(), 0);
+    // With a slot free again, the upload gets as far as the kernel.
+    assert_eq!(dev.upload_ff_effect(&rumble).err(), Some(DeviceError::Nix(Error::Sys(::nix::Errno::ENOTTY))));
+    unsafe { libc::close(write_fd); }
+}
+
+#[test]
+fn ff_status_is_tracked_per_effect() {
+    let (mut dev, write_fd) = pipe_device();
+    let report = (0, SYN_REPORT as u16, 0);
+    write_events(write_fd, &[(0x17, 2, 1), (0x17, 3, 0), report]);
+    assert_eq!(read_events(&mut dev).len(), 3);
+    assert_eq!(dev.ff_effect_status(2), Some(FF_STATUS_PLAYING));
+    assert_eq!(dev.ff_effect_status(3), Some(FF_STATUS_STOPPED));
+    assert_eq!(dev.ff_effect_status(4), None);
+    // Statuses the kernel doesn't define are ignored.
+    write_events(write_fd, &[(0x17, 2, 0), (0x17, 4, 7), report]);
+    read_events(&mut dev);
+    assert_eq!(dev.ff_effect_status(2), Some(FF_STATUS_STOPPED));
+    assert_eq!(dev.ff_effect_status(4), None);
+    unsafe { libc::close(write_fd); }
+}
+
+#[test]
+fn ff_gain_and_autocenter_are_written_as_events() {
+    let (mut dev, read_fd) = pipe_writer_device();
+    dev.ty = FORCEFEEDBACK;
+    dev.ff.insert(FF_GAIN as usize);
+    assert_eq!(dev.set_ff_autocenter(0x8000), Err(Error::UnsupportedOperation));
+    dev.set_ff_gain(0xffff).unwrap();
+    assert_eq!(read_written(read_fd), vec![(0x15, 0x60, 0xffff), (0, SYN_REPORT as u16, 0)]);
+    dev.ff.insert(FF_AUTOCENTER as usize);
+    dev.set_ff_autocenter(0x8000).unwrap();
+    assert_eq!(read_written(read_fd), vec![(0x15, 0x61, 0x8000), (0, SYN_REPORT as u16, 0)]);
+    unsafe { libc::close(read_fd); }
+}
+
+/// A virtual keyboard and its `/dev/input` node, or `None` if virtual devices can't be created
+/// here (e.g. `/dev/uinput` is missing or not writable), in which case the test is skipped.
+fn uinput_keyboard() -> Option<(uinput::VirtualDevice, std::path::PathBuf)> {
+    let vdev = match uinput::VirtualDeviceBuilder::new("evdev test keyboard").with_keys(&[KEY_A]).build() {
+        Ok(vdev) => vdev,
+        Err(_) => return None,
+    };
+    // The node shows up asynchronously.
+    for _ in 0..100 {
+        if let Ok(path) = vdev.dev_node() {
+            if path.exists() {
+                return Some((vdev, path));
+            }
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    None
+}
+
+#[test]
+fn only_ebusy_means_already_grabbed() {
+    let (mut dev, write_fd) = pipe_device();
+    // A pipe isn't an evdev device at all, which mustn't be mistaken for someone else's grab.
+    assert_eq!(dev.grab().err(), Some(Error::Sys(::nix::Errno::ENOTTY)));
+    assert_eq!(dev.try_grab().err(), Some(DeviceError::Nix(Error::Sys(::nix::Errno::ENOTTY))));
+    unsafe { libc::close(write_fd); }
+}
+
+#[test]
+fn grabbing_a_grabbed_device_is_refused() {
+    let (_vdev, path) = match uinput_keyboard() {
+        Some(vdev) => vdev,
+        None => return,
+    };
+    let mut first = Device::open(&path).unwrap();
+    let mut second = Device::open(&path).unwrap();
+    {
+        let _grab = first.grab().unwrap();
+        assert_eq!(second.try_grab().err(), Some(DeviceError::AlreadyGrabbed));
+        assert_eq!(second.grab().err(), Some(Error::Sys(::nix::Errno::EBUSY)));
+    }
+    // Dropping the guard released the grab.
+    assert!(second.try_grab().is_ok());
+}
+
+#[test]
+fn repeat_settings_are_shown_in_milliseconds() {
+    let (mut dev, write_fd) = pipe_device();
+    assert_eq!(to_millis(Duration::new(1, 999_999)), 1000);
+    assert_eq!(to_millis(Duration::from_secs(u64::MAX)), libc::c_uint::MAX);
+    // The settings are only recorded once the kernel has taken them.
+    assert!(dev.set_repeat_settings(Duration::from_millis(250), Duration::from_millis(33)).is_err());
+    assert_eq!(dev.state().rep_vals, None);
+    dev.ty = REPEAT;
+    dev.state.rep_vals = Some((Duration::from_millis(250), Duration::from_millis(33)));
+    assert!(dev.to_string().contains("    Delay: 250ms, period: 33ms\n"));
+    unsafe { libc::close(write_fd); }
+}
+
+#[test]
+fn revoked_device_refuses_reads() {
+    let (_vdev, path) = match uinput_keyboard() {
+        Some(vdev) => vdev,
+        None => return,
+    };
+    let mut dev = Device::open(&path).unwrap();
+    let mut other = Device::open(&path).unwrap();
+    dev.revoke().unwrap();
+    assert!(dev.is_revoked());
+    assert_eq!(dev.events().err(), Some(DeviceError::Revoked));
+    assert_eq!(dev.sync_state(), Err(DeviceError::Revoked));
+    assert_eq!(dev.grab().err(), Some(Error::Sys(::nix::Errno::ENODEV)));
+    // Other descriptors for the device are unaffected.
+    assert_eq!(other.sync_state(), Ok(()));
+}
+
+#[test]
+fn events_are_stamped_with_the_chosen_clock() {
+    let (mut vdev, path) = match uinput_keyboard() {
+        Some(vdev) => vdev,
+        None => return,
+    };
+    let mut dev = Device::open(&path).unwrap();
+    dev.set_clock(Clock::Monotonic).unwrap();
+    assert_eq!(dev.clock(), Clock::Monotonic);
+    let before = Clock::Monotonic.now();
+    vdev.emit(&[ev(1, 30, 1)]).unwrap();
+    let events: Vec<_> = dev.events().unwrap().collect();
+    assert_eq!(events.len(), 2);
+    // A realtime stamp would be decades after the monotonic clock.
+    let time = dev.event_time(&events[0]);
+    assert!(time.duration_since(before).unwrap() < Duration::from_secs(10));
+}
@@ -0,0 +1,366 @@
+//! Multitouch contact tracking.
+//!
+//! Devices following the slotted ("type B") multitouch protocol described in the kernel's
+//! `multi-touch-protocol.txt` report each contact in a slot, and only send the `ABS_MT_*` values
+//! that changed, for the slots that changed. `TouchTracker` does the bookkeeping to turn that back
+//! into a complete list of contacts for every frame. Older devices that use the anonymous
+//! ("type A") protocol instead can be handled the same way by converting their events with
+//! `ProtocolAConverter` first.
+//!
+//! ```no_run
+//! use evdev::touch::{TouchPhase, TouchTracker};
+//!
+//! let mut dev = evdev::Device::open(&"/dev/input/event0").unwrap();
+//! let mut tracker = TouchTracker::new(&dev);
+//! loop {
+//!     for ev in dev.events().unwrap() {
+//!         if let Some(contacts) = tracker.process(&ev) {
+//!             for contact in contacts.iter().filter(|c| c.phase == TouchPhase::Began) {
+//!                 println!("touch {} at {},{}", contact.tracking_id, contact.x, contact.y);
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+
+use raw::input_event;
+use {Device, ABSOLUTE, SYNCHRONIZATION, SYN_REPORT, SYN_MT_REPORT, SYN_DROPPED, MT_AXES};
+use {ABS_MT_SLOT, ABS_MT_TOUCH_MAJOR, ABS_MT_TOUCH_MINOR, ABS_MT_WIDTH_MAJOR, ABS_MT_WIDTH_MINOR};
+use {ABS_MT_ORIENTATION, ABS_MT_POSITION_X, ABS_MT_POSITION_Y, ABS_MT_TOOL_TYPE};
+use {ABS_MT_TRACKING_ID, ABS_MT_PRESSURE, AbsoluteAxis};
+
+/// What happened to a contact in a frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TouchPhase {
+    /// The contact touched down in this frame.
+    Began,
+    /// The contact was already down, and still is. Its values may or may not have changed.
+    Moved,
+    /// The contact lifted in this frame. Its values are the last ones it had.
+    Ended,
+}
+
+/// A single contact, in the units the device reports (see `Device::state` for the ranges).
+/// Values the device doesn't report are 0.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Contact {
+    pub slot: usize,
+    /// Identifies the contact for as long as it is down. Ids are not reused until they wrap
+    /// around, so a contact that lifts and touches down again gets a new one.
+    pub tracking_id: i32,
+    pub phase: TouchPhase,
+    pub x: i32,
+    pub y: i32,
+    pub pressure: i32,
+    /// Length of the major and minor axes of the contact area.
+    pub touch_major: i32,
+    pub touch_minor: i32,
+    /// Length of the major and minor axes of the approaching tool (e.g. the whole finger).
+    pub width_major: i32,
+    pub width_minor: i32,
+    pub orientation: i32,
+    /// `MT_TOOL_FINGER` (0), `MT_TOOL_PEN` (1) or `MT_TOOL_PALM` (2).
+    pub tool_type: i32,
+}
+
+fn get(vals: &[i32; MT_AXES], axis: AbsoluteAxis) -> i32 {
+    vals[::mt_index(axis.number()).unwrap()]
+}
+
+impl Contact {
+    fn new(slot: usize, vals: &[i32; MT_AXES], phase: TouchPhase) -> Contact {
+        Contact {
+            slot,
+            tracking_id: get(vals, ABS_MT_TRACKING_ID),
+            phase,
+            x: get(vals, ABS_MT_POSITION_X),
+            y: get(vals, ABS_MT_POSITION_Y),
+            pressure: get(vals, ABS_MT_PRESSURE),
+            touch_major: get(vals, ABS_MT_TOUCH_MAJOR),
+            touch_minor: get(vals, ABS_MT_TOUCH_MINOR),
+            width_major: get(vals, ABS_MT_WIDTH_MAJOR),
+            width_minor: get(vals, ABS_MT_WIDTH_MINOR),
+            orientation: get(vals, ABS_MT_ORIENTATION),
+            tool_type: get(vals, ABS_MT_TOOL_TYPE),
+        }
+    }
+}
+
+/// Builds the contacts of each frame out of a stream of type B multitouch events.
+///
+/// Feed it every event read from the device, in order, with `process`. The events should come
+/// from `Device::events`, which makes up for dropped events; after a `SYN_DROPPED` in the raw
+/// stream, the tracker just skips to the next frame.
+#[derive(Clone, Debug)]
+pub struct TouchTracker {
+    slot: i32,
+    // The `ABS_MT_*` values of each slot as of the last frame, and as updated by the current one.
+    committed: Vec<[i32; MT_AXES]>,
+    current: Vec<[i32; MT_AXES]>,
+    frame: Vec<Contact>,
+    dropping: bool,
+}
+
+impl TouchTracker {
+    /// A tracker for `dev`, starting out with the contacts its `DeviceState` knows about.
+    pub fn new(dev: &Device) -> TouchTracker {
+        let state = dev.state();
+        let mut tracker = TouchTracker::with_slots(state.mt_vals.len());
+        tracker.committed = state.mt_vals.clone();
+        tracker.current = state.mt_vals.clone();
+        if let Some(info) = state.abs_vals.get(ABS_MT_SLOT.number::<usize>()) {
+            tracker.slot = info.value;
+        }
+        tracker
+    }
+
+    /// A tracker for a stream with `slots` slots, all of them empty, where no `ABS_MT_SLOT` has
+    /// been seen yet.
+    pub fn with_slots(slots: usize) -> TouchTracker {
+        let mut empty = [0; MT_AXES];
+        empty[tracking_idx()] = -1;
+        TouchTracker {
+            slot: 0,
+            committed: vec![empty; slots],
+            current: vec![empty; slots],
+            frame: Vec::new(),
+            dropping: false,
+        }
+    }
+
+    /// Takes in one event. At the end of each frame, returns every contact that is down or that
+    /// lifted during the frame, in slot order.
+    ///
+    /// A slot whose contact is replaced within a single frame shows up twice: once as `Ended` for
+    /// the old contact, and once as `Began` for the new one.
+    pub fn process(&mut self, ev: &input_event) -> Option<&[Contact]> {
+        if ev._type == SYNCHRONIZATION.number::<u16>() {
+            if ev.code == SYN_DROPPED as u16 {
+                self.dropping = true;
+                self.current.clone_from(&self.committed);
+            } else if ev.code == SYN_REPORT as u16 {
+                if self.dropping {
+                    self.dropping = false;
+                    return None;
+                }
+                self.end_frame();
+                return Some(&self.frame);
+            }
+        } else if ev._type == ABSOLUTE.number::<u16>() && !self.dropping {
+            if ev.code == ABS_MT_SLOT.number::<u16>() {
+                self.slot = ev.value;
+            } else if let Some(idx) = ::mt_index(ev.code) {
+                if let Some(vals) = self.current.get_mut(self.slot as usize) {
+                    vals[idx] = ev.value;
+                }
+            }
+        }
+        None
+    }
+
+    /// The contacts that are currently down, as of the last complete frame.
+    pub fn contacts(&self) -> Vec<Contact> {
+        self.committed.iter().enumerate()
+            .filter(|&(_, vals)| get(vals, ABS_MT_TRACKING_ID) != -1)
+            .map(|(slot, vals)| Contact::new(slot, vals, TouchPhase::Moved))
+            .collect()
+    }
+
+    fn end_frame(&mut self) {
+        self.frame.clear();
+        for (slot, (old, new)) in self.committed.iter().zip(&self.current).enumerate() {
+            let old_id = get(old, ABS_MT_TRACKING_ID);
+            let new_id = get(new, ABS_MT_TRACKING_ID);
+            if old_id != -1 && old_id != new_id {
+                self.frame.push(Contact::new(slot, old, TouchPhase::Ended));
+            }
+            if new_id != -1 {
+                let phase = if old_id == new_id { TouchPhase::Moved } else { TouchPhase::Began };
+                self.frame.push(Contact::new(slot, new, phase));
+            }
+        }
+        self.committed.clone_from(&self.current);
+    }
+}
+
+/// Converts the events of an anonymous ("type A") multitouch device into a type B stream, for
+/// `TouchTracker` or anything else that only understands slots.
+///
+/// Type A devices send the values of every contact in every frame, separating contacts with
+/// `SYN_MT_REPORT`, and don't say which contact is which. The converter puts each contact in the
+/// slot of the contact nearest to it in the previous frame, and gives contacts that don't have
+/// one a free slot and a new tracking id. Events other than `ABS_MT_*` pass through unchanged.
+///
+/// Matching is purely by distance, so a finger that lifts in the same frame as another one
+/// touches down elsewhere looks like it moved there. Contacts that don't fit in the slots are
+/// dropped.
+#[derive(Clone, Debug)]
+pub struct ProtocolAConverter {
+    slots: Vec<[i32; MT_AXES]>,
+    // The `mt_index`es of the ABS_MT_* codes seen so far, as a bit mask. Only these are emitted.
+    axes: u32,
+    // The slot the output stream has selected.
+    slot: i32,
+    next_id: i32,
+    // The contact being reported, and the ones reported so far in the current frame.
+    contact: Option<[i32; MT_AXES]>,
+    contacts: Vec<[i32; MT_AXES]>,
+    // Events of the current frame that aren't ABS_MT_*.
+    pending: Vec<input_event>,
+    out: Vec<input_event>,
+    dropping: bool,
+}
+
+impl ProtocolAConverter {
+    /// A converter that tracks up to `slots` contacts at once.
+    pub fn new(slots: usize) -> ProtocolAConverter {
+        let mut empty = [0; MT_AXES];
+        empty[tracking_idx()] = -1;
+        ProtocolAConverter {
+            slots: vec![empty; slots],
+            axes: 0,
+            slot: 0,
+            next_id: 0,
+            contact: None,
+            contacts: Vec::new(),
+            pending: Vec::new(),
+            out: Vec::new(),
+            dropping: false,
+        }
+    }
+
+    /// Takes in one event of the type A stream. Returns the type B events it turns into, if any:
+    /// the events of a whole frame at each `SYN_REPORT`, and a `SYN_DROPPED` as soon as one comes
+    /// in.
+    pub fn process(&mut self, ev: &input_event) -> Option<&[input_event]> {
+        self.out.clear();
+        let syn = ev._type == SYNCHRONIZATION.number::<u16>();
+        if self.dropping {
+            // The partial frame after the drop carries on until the next SYN_REPORT, which is
+            // passed on so that consumers know the drop is over.
+            if syn && ev.code == SYN_REPORT as u16 {
+                self.dropping = false;
+                self.out.push(*ev);
+                return Some(&self.out);
+            }
+            return None;
+        }
+        if syn && ev.code == SYN_DROPPED as u16 {
+            self.dropping = true;
+            self.contact = None;
+            self.contacts.clear();
+            self.pending.clear();
+            self.out.push(*ev);
+            return Some(&self.out);
+        }
+        if syn && ev.code == SYN_MT_REPORT as u16 {
+            if let Some(contact) = self.contact.take() {
+                self.contacts.push(contact);
+            }
+            return None;
+        }
+        if syn && ev.code == SYN_REPORT as u16 {
+            // Be lenient about a missing SYN_MT_REPORT after the last contact.
+            if let Some(contact) = self.contact.take() {
+                self.contacts.push(contact);
+            }
+            self.end_frame(ev);
+            return Some(&self.out);
+        }
+        if ev._type == ABSOLUTE.number::<u16>() {
+            if let Some(idx) = ::mt_index(ev.code) {
+                // Tracking ids are ours to assign.
+                if idx != tracking_idx() {
+                    self.axes |= 1 << idx;
+                    self.contact.get_or_insert([0; MT_AXES])[idx] = ev.value;
+                }
+                return None;
+            }
+        }
+        self.pending.push(*ev);
+        None
+    }
+
+    fn end_frame(&mut self, report: &input_event) {
+        let tracking = tracking_idx();
+        let pos = |vals: &[i32; MT_AXES]| (get(vals, ABS_MT_POSITION_X) as i64, get(vals, ABS_MT_POSITION_Y) as i64);
+
+        // Match the closest pairs of old and new contacts first.
+        let mut pairs = Vec::new();
+        for (slot, old) in self.slots.iter().enumerate().filter(|&(_, old)| old[tracking] != -1) {
+            let (ox, oy) = pos(old);
+            for (idx, new) in self.contacts.iter().enumerate() {
+                let (nx, ny) = pos(new);
+                pairs.push(((ox - nx).pow(2) + (oy - ny).pow(2), slot, idx));
+            }
+        }
+        pairs.sort();
+        let mut slot_of = vec![None; self.contacts.len()];
+        let mut taken = vec![false; self.slots.len()];
+        for (_, slot, idx) in pairs {
+            if slot_of[idx].is_none() && !taken[slot] {
+                slot_of[idx] = Some(slot);
+                taken[slot] = true;
+            }
+        }
+        // New contacts go in empty slots, and then in the slots of contacts that just lifted.
+        let mut free = (0..self.slots.len()).filter(|&slot| !taken[slot])
+            .filter(|&slot| self.slots[slot][tracking] == -1)
+            .chain((0..self.slots.len()).filter(|&slot| !taken[slot] && self.slots[slot][tracking] != -1))
+            .collect::<Vec<_>>()
+            .into_iter();
+
+        let mut new_slots = self.slots.clone();
+        for vals in &mut new_slots {
+            vals[tracking] = -1;
+        }
+        for (idx, contact) in self.contacts.iter().enumerate() {
+            let (slot, id) = match slot_of[idx] {
+                Some(slot) => (slot, self.slots[slot][tracking]),
+                None => match free.next() {
+                    Some(slot) => {
+                        let id = self.next_id;
+                        self.next_id = (self.next_id + 1) & 0xffff;
+                        (slot, id)
+                    }
+                    None => continue,
+                },
+            };
+            new_slots[slot] = *contact;
+            new_slots[slot][tracking] = id;
+        }
+
+        let slot_code = ABS_MT_SLOT.number::<u16>();
+        let first = slot_code + 1;
+        for (slot, (old, new)) in self.slots.iter().zip(&new_slots).enumerate() {
+            for idx in 0..MT_AXES {
+                if old[idx] == new[idx] || (idx != tracking && self.axes & 1 << idx == 0) {
+                    continue;
+                }
+                if self.slot != slot as i32 {
+                    self.slot = slot as i32;
+                    self.out.push(input_event {
+                        _type: ABSOLUTE.number(),
+                        code: slot_code,
+                        value: self.slot,
+                        ..*report
+                    });
+                }
+                self.out.push(input_event {
+                    _type: ABSOLUTE.number(),
+                    code: first + idx as u16,
+                    value: new[idx],
+                    ..*report
+                });
+            }
+        }
+        self.out.append(&mut self.pending);
+        self.out.push(*report);
+        self.slots = new_slots;
+        self.contacts.clear();
+    }
+}
+
+fn tracking_idx() -> usize {
+    ::mt_index(ABS_MT_TRACKING_ID.number()).unwrap()
+}
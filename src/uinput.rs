@@ -0,0 +1,261 @@
+//! Virtual input devices, created through `/dev/uinput`.
+//!
+//! A virtual device looks exactly like a hardware device to the rest of the system: it gets its
+//! own `/dev/input/eventN` node, shows up in `enumerate`, and whatever events are emitted on it are
+//! delivered to every reader of that node. This is handy for remapping input (grab the physical
+//! device, emit the translated events on a virtual one) and for testing code that consumes evdev
+//! events without needing real hardware.
+//!
+//! ```no_run
+//! use evdev::uinput::VirtualDeviceBuilder;
+//!
+//! let mut dev = VirtualDeviceBuilder::new("my virtual keyboard")
+//!     .with_keys(&[evdev::KEY_A, evdev::KEY_B])
+//!     .build()
+//!     .unwrap();
+//! dev.emit(&[evdev::raw::input_event { _type: 1, code: evdev::KEY_A as u16, value: 1, ..Default::default() }])
+//!    .unwrap();
+//! ```
+
+use std::ffi::CStr;
+use std::os::unix::io::*;
+use std::path::PathBuf;
+
+use libc;
+use nix::Error;
+
+use raw::*;
+use {Types, Props, Key, RelativeAxis, AbsoluteAxis, Switch, Led, Sound, Misc};
+use {KEY, RELATIVE, ABSOLUTE, MISC, SWITCH, LED, SOUND};
+
+/// Describes a virtual device, which is then created with `build`.
+///
+/// Only the capabilities that are explicitly added will be advertised by the device. Events of
+/// any other type or code that are emitted on it are silently dropped by the kernel.
+#[derive(Clone, Debug)]
+pub struct VirtualDeviceBuilder {
+    name: Vec<u8>,
+    id: input_id,
+    props: Props,
+    keys: Vec<Key>,
+    rel: RelativeAxis,
+    abs: Vec<(AbsoluteAxis, input_absinfo)>,
+    switch: Switch,
+    led: Led,
+    snd: Sound,
+    misc: Misc,
+}
+
+impl VirtualDeviceBuilder {
+    /// Starts describing a device called `name`, which will be on the "Virtual" bus with no vendor
+    /// or product ID unless `input_id` is called.
+    pub fn new(name: &str) -> VirtualDeviceBuilder {
+        VirtualDeviceBuilder {
+            name: name.as_bytes().to_vec(),
+            id: input_id { bustype: 0x6, vendor: 0, product: 0, version: 0 },
+            props: Props::empty(),
+            keys: Vec::new(),
+            rel: RelativeAxis::empty(),
+            abs: Vec::new(),
+            switch: Switch::empty(),
+            led: Led::empty(),
+            snd: Sound::empty(),
+            misc: Misc::empty(),
+        }
+    }
+
+    pub fn input_id(mut self, id: input_id) -> VirtualDeviceBuilder {
+        self.id = id;
+        self
+    }
+
+    pub fn with_properties(mut self, props: Props) -> VirtualDeviceBuilder {
+        self.props |= props;
+        self
+    }
+
+    pub fn with_keys(mut self, keys: &[Key]) -> VirtualDeviceBuilder {
+        self.keys.extend_from_slice(keys);
+        self
+    }
+
+    pub fn with_relative_axes(mut self, axes: RelativeAxis) -> VirtualDeviceBuilder {
+        self.rel |= axes;
+        self
+    }
+
+    /// Adds a single absolute axis. `info.value` is the initial value of the axis.
+    ///
+    /// `axis` must be exactly one axis, or `build` fails with `UnsupportedOperation`.
+    pub fn with_absolute_axis(mut self, axis: AbsoluteAxis, info: input_absinfo) -> VirtualDeviceBuilder {
+        self.abs.push((axis, info));
+        self
+    }
+
+    pub fn with_switches(mut self, switches: Switch) -> VirtualDeviceBuilder {
+        self.switch |= switches;
+        self
+    }
+
+    pub fn with_leds(mut self, leds: Led) -> VirtualDeviceBuilder {
+        self.led |= leds;
+        self
+    }
+
+    pub fn with_sounds(mut self, sounds: Sound) -> VirtualDeviceBuilder {
+        self.snd |= sounds;
+        self
+    }
+
+    pub fn with_misc(mut self, misc: Misc) -> VirtualDeviceBuilder {
+        self.misc |= misc;
+        self
+    }
+
+    /// Creates the device. Requires write access to `/dev/uinput` and a kernel new enough to
+    /// support `UI_DEV_SETUP` (4.5 or later).
+    ///
+    /// Fails with `EINVAL` if the name contains a NUL byte or is longer than the kernel allows,
+    /// and with `UnsupportedOperation` if an absolute axis was added that isn't exactly one axis.
+    pub fn build(&self) -> Result<VirtualDevice, Error> {
+        let mut setup = uinput_setup::default();
+        if self.name.contains(&0) || self.name.len() >= UINPUT_MAX_NAME_SIZE {
+            return Err(Error::Sys(::nix::Errno::EINVAL));
+        }
+        if self.abs.iter().any(|&(axis, _)| axis.bits().count_ones() != 1) {
+            return Err(Error::UnsupportedOperation);
+        }
+        setup.name[..self.name.len()].copy_from_slice(&self.name);
+        setup.id = self.id;
+
+        let fd = unsafe {
+            libc::open(b"/dev/uinput\0".as_ptr() as *const libc::c_char,
+                       libc::O_NONBLOCK | libc::O_WRONLY | libc::O_CLOEXEC, 0)
+        };
+        if fd == -1 {
+            return Err(Error::from_errno(::nix::Errno::last()));
+        }
+        // From here on, dropping `dev` cleans up the fd if anything goes wrong.
+        let dev = VirtualDevice { fd };
+
+        for idx in set_bits(self.props.bits() as u64) {
+            do_ioctl!(ui_set_propbit(fd, idx));
+        }
+
+        if !self.keys.is_empty() {
+            set_type(fd, KEY)?;
+            for key in &self.keys {
+                do_ioctl!(ui_set_keybit(fd, *key as libc::c_int));
+            }
+        }
+        if !self.rel.is_empty() {
+            set_type(fd, RELATIVE)?;
+            for idx in set_bits(self.rel.bits() as u64) {
+                do_ioctl!(ui_set_relbit(fd, idx));
+            }
+        }
+        if !self.abs.is_empty() {
+            set_type(fd, ABSOLUTE)?;
+            for &(axis, info) in &self.abs {
+                let abs_setup = uinput_abs_setup { code: axis.number(), absinfo: info };
+                do_ioctl!(ui_set_absbit(fd, axis.number()));
+                do_ioctl!(ui_abs_setup(fd, &abs_setup));
+            }
+        }
+        if !self.switch.is_empty() {
+            set_type(fd, SWITCH)?;
+            for idx in set_bits(self.switch.bits() as u64) {
+                do_ioctl!(ui_set_swbit(fd, idx));
+            }
+        }
+        if !self.led.is_empty() {
+            set_type(fd, LED)?;
+            for idx in set_bits(self.led.bits() as u64) {
+                do_ioctl!(ui_set_ledbit(fd, idx));
+            }
+        }
+        if !self.snd.is_empty() {
+            set_type(fd, SOUND)?;
+            for idx in set_bits(self.snd.bits() as u64) {
+                do_ioctl!(ui_set_sndbit(fd, idx));
+            }
+        }
+        if !self.misc.is_empty() {
+            set_type(fd, MISC)?;
+            for idx in set_bits(self.misc.bits() as u64) {
+                do_ioctl!(ui_set_mscbit(fd, idx));
+            }
+        }
+
+        do_ioctl!(ui_dev_setup(fd, &setup));
+        do_ioctl!(ui_dev_create(fd));
+
+        Ok(dev)
+    }
+}
+
+fn set_type(fd: RawFd, ty: Types) -> Result<(), Error> {
+    do_ioctl!(ui_set_evbit(fd, ty.number()));
+    Ok(())
+}
+
+/// Indices of the set bits in `bits`, lowest first.
+fn set_bits(bits: u64) -> impl Iterator<Item = libc::c_int> {
+    (0..64).filter(move |idx| bits & (1 << idx) != 0)
+}
+
+/// A virtual device created by `VirtualDeviceBuilder`. The device is destroyed when this is dropped.
+#[derive(Debug)]
+pub struct VirtualDevice {
+    fd: RawFd,
+}
+
+impl VirtualDevice {
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Emits `events` as a single packet, appending a `SYN_REPORT` if the last event is not
+    /// already one. The timestamps are ignored; the kernel stamps the events itself.
+    pub fn emit(&mut self, events: &[input_event]) -> Result<(), Error> {
+        ::write_frame(self.fd, events)
+    }
+
+    /// The sysfs directory of the device, e.g. `/sys/devices/virtual/input/input42`.
+    pub fn sys_path(&self) -> Result<PathBuf, Error> {
+        let mut buf = [0u8; 64];
+        do_ioctl!(ui_get_sysname(self.fd, &mut buf[..]));
+        let last = buf.len() - 1;
+        buf[last] = 0;
+        let name = unsafe { CStr::from_ptr(buf.as_ptr() as *const libc::c_char) };
+        Ok(PathBuf::from("/sys/devices/virtual/input").join(&*name.to_string_lossy()))
+    }
+
+    /// The `/dev/input/eventN` node of the device, which can be passed to `Device::open`.
+    ///
+    /// The node is created asynchronously by udev (or devtmpfs), so it may not exist yet right
+    /// after the device is built.
+    pub fn dev_node(&self) -> Result<PathBuf, Error> {
+        let dir = std::fs::read_dir(self.sys_path()?).map_err(|e| {
+            Error::Sys(::nix::Errno::from_i32(e.raw_os_error().unwrap_or(libc::EIO)))
+        })?;
+        for entry in dir.filter_map(|e| e.ok()) {
+            let name = entry.file_name();
+            if name.to_string_lossy().starts_with("event") {
+                return Ok(PathBuf::from("/dev/input").join(name));
+            }
+        }
+        Err(Error::Sys(::nix::Errno::ENOENT))
+    }
+}
+
+impl Drop for VirtualDevice {
+    fn drop(&mut self) {
+        // Fails harmlessly if the device was never created. Closing would destroy it anyway,
+        // but being explicit doesn't hurt.
+        unsafe {
+            let _ = ui_dev_destroy(self.fd);
+            libc::close(self.fd);
+        }
+    }
+}
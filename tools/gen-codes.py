@@ -0,0 +1,224 @@
+#!/usr/bin/env python3
+"""Generates the event code tables from include/linux/input-event-codes.h.
+
+Writes src/codes.rs, which has the `Key` enum and the bitflags for the other event codes, and
+src/code_names.rs, which has the name tables used by the `names` module. Run it from the root of
+the repository after updating the header:
+
+    cp /usr/include/linux/input-event-codes.h include/linux/
+    python3 tools/gen-codes.py
+
+The `generated_tables_match_header` test fails when the tables are out of date.
+
+The vendored header is the unmodified include/uapi/linux/input-event-codes.h of Linux 6.1. Codes
+added by later kernels that the crate already supports are listed in `NEWER` instead.
+"""
+
+import re
+import sys
+
+HEADER = 'include/linux/input-event-codes.h'
+
+# Bitflags generated for each prefix: (prefix, type, bits, doc).
+FLAGS = [
+    ('REL_', 'RelativeAxis', 'u32', None),
+    ('ABS_', 'AbsoluteAxis', 'u64', None),
+    ('SW_', 'Switch', 'u32', None),
+    ('LED_', 'Led', 'u32', 'LEDs specified by USB HID.'),
+    ('MSC_', 'Misc', 'u32', 'Various miscellaneous event types.'),
+    ('REP_', 'Repeat', 'u32', None),
+    ('SND_', 'Sound', 'u32', None),
+]
+
+# Prefixes whose `<prefix>MAX` was once one of the flags. They're kept as deprecated constants of
+# the flags type, outside the flags so that `all()` only has real codes.
+DEPRECATED_MAX = ['SW_', 'LED_', 'MSC_']
+
+# Name tables generated for the `names` module: (table, prefix).
+NAMES = [
+    ('EV_NAMES', 'EV_'),
+    ('SYN_NAMES', 'SYN_'),
+    ('REL_NAMES', 'REL_'),
+    ('ABS_NAMES', 'ABS_'),
+    ('SW_NAMES', 'SW_'),
+    ('LED_NAMES', 'LED_'),
+    ('MSC_NAMES', 'MSC_'),
+    ('SND_NAMES', 'SND_'),
+    ('REP_NAMES', 'REP_'),
+    ('INPUT_PROP_NAMES', 'INPUT_PROP_'),
+]
+
+# Codes from kernels newer than the vendored header, as (name, value, comment, version), which are
+# treated as if they came at the end of the header.
+NEWER = [
+    ('INPUT_PROP_PRESSUREPAD', '0x07', 'pressure triggers clicks', '6.13'),
+]
+
+# Docs for codes the header doesn't comment on. Comments from the header are used as docs in
+# quotes.
+DOCS = {
+    'REL_RESERVED': 'Reserved. Old kernels sent `REL_MISC + 1` here for HID devices.',
+    'REL_WHEEL_HI_RES': 'High resolution `REL_WHEEL`, in 1/120ths of a notch.',
+    'REL_HWHEEL_HI_RES': 'High resolution `REL_HWHEEL`, in 1/120ths of a notch.',
+    'ABS_PROFILE': 'The profile selected with a switch on the device, e.g. on gaming mice.',
+    'ABS_RESERVED': 'Reserved. Old kernels sent `ABS_MISC + 6` here for HID devices.',
+    'LED_SLEEP': '"Stand-by"',
+    'LED_MISC': '"Generic indicator"',
+    'LED_MAIL': '"Message waiting"',
+    'LED_CHARGING': '"External power connected"',
+    'MSC_SERIAL': 'Serial number, only exported for tablets ("Transducer Serial Number")',
+    'MSC_PULSELED': 'Only used by the PowerMate driver, right now.',
+    'MSC_GESTURE': 'Completely unused.',
+    'MSC_RAW': '"Raw" event, rarely used.',
+    'MSC_SCAN': 'Key scancode',
+    'MSC_TIMESTAMP': 'Microseconds since the last reset, as counted by the device. Sent by some '
+                     'touchpads and tablets with each frame, so that the time between frames is '
+                     'known even when the events were delayed on their way to the host.',
+}
+
+DEFINE = re.compile(r'#define\s+(\w+)\s+(\(.*?\)|\S+)\s*(?:/\*\s*(.*?)\s*(\*/|$))?')
+
+
+def parse(path):
+    """The `#define`s in the header, as (name, value, comment) in order."""
+    defines = []
+    lines = iter(open(path).read().splitlines())
+    for line in lines:
+        m = DEFINE.match(line)
+        if not m:
+            continue
+        name, value, comment, end = m.groups()
+        if comment is not None and end != '*/':
+            # The comment goes on for more lines.
+            for more in lines:
+                comment += ' ' + more.split('*/')[0].strip()
+                if '*/' in more:
+                    break
+        defines.append((name, value, comment))
+    return defines
+
+
+def codes(defines, prefixes):
+    """The codes with names starting with one of `prefixes`, as (name, literal, comment, number,
+    primary) in header order, where `primary` is the name to use for the code.
+
+    Names defined as other names are aliases, and so are names defined with the same number as a
+    later name: the header defines the start of a range before the first code in it (e.g.
+    `BTN_MISC` before `BTN_0`), and the code is the one to name it by.
+    """
+    values = {name: value for name, value, _ in defines}
+    found = []
+    for name, value, comment in defines:
+        if not name.startswith(prefixes) or re.search(r'_(MAX|CNT)$', name):
+            continue
+        if name == 'EV_VERSION':
+            continue
+        number = value
+        while number in values:
+            number = values[number]
+        try:
+            number = int(number, 0)
+        except ValueError:
+            continue
+        found.append((name, value, comment, number))
+    primaries = {}
+    for name, value, _, number in found:
+        if value not in values:
+            primaries[number] = name
+    return [(name, value, comment, number, primaries[number])
+            for name, value, comment, number in found]
+
+
+def doc(name, comment):
+    if name in DOCS:
+        return DOCS[name]
+    if comment:
+        return '"%s"' % comment
+    return None
+
+
+HEADER_NOTE = '// Generated by tools/gen-codes.py from include/linux/input-event-codes.h. Do not edit.'
+
+
+def gen_codes(defines):
+    values = dict((name, value) for name, value, _ in defines)
+    out = [HEADER_NOTE, '']
+    keys = codes(defines, ('KEY_', 'BTN_'))
+    keys.sort(key=lambda key: key[3])
+    out.append('keys! {')
+    for name, value, _, _, primary in keys:
+        if name == primary:
+            out.append('    %s = %s,' % (name, value))
+    out.append('}')
+    out.append('')
+    out.append('/// The highest key code, e.g. for sizing bitmaps of keys. Not a key itself.')
+    out.append('pub const KEY_MAX: u16 = %s;' % values['KEY_MAX'])
+    out.append('')
+    out.append('/// Names the kernel headers define as aliases for other keys.')
+    out.append('const KEY_ALIASES: &[(&str, Key)] = &[')
+    for name, _, _, _, primary in keys:
+        if name != primary:
+            out.append('    ("%s", Key::%s),' % (name, primary))
+    out.append('];')
+    out.append('')
+    out.append('/// The aliases as constants, e.g. `Key::BTN_A`.')
+    out.append('impl Key {')
+    for name, _, _, _, primary in keys:
+        if name != primary:
+            out.append('    pub const %s: Key = Key::%s;' % (name, primary))
+    out.append('}')
+    out.append('')
+    out.append('// And at the crate root, where `pub use Key::*` puts the keys themselves.')
+    for name, _, _, _, primary in keys:
+        if name != primary:
+            out.append('pub const %s: Key = Key::%s;' % (name, primary))
+    for prefix, ty, bits, type_doc in FLAGS:
+        out.append('')
+        out.append('bitflags! {')
+        if type_doc:
+            out.append('    /// %s' % type_doc)
+        out.append('    pub flags %s: %s {' % (ty, bits))
+        for name, _, comment, number, primary in codes(defines, prefix):
+            if name != primary:
+                continue
+            text = doc(name, comment)
+            if text:
+                out.append('        /// %s' % text)
+            out.append('        const %s = 1 << 0x%02x,' % (name, number))
+        out.append('    }')
+        out.append('}')
+        if prefix in DEPRECATED_MAX:
+            name = prefix + 'MAX'
+            out.append('')
+            out.append('#[deprecated(note = "the highest code rather than a flag of its own")]')
+            out.append('pub const %s: %s = %s { bits: 1 << 0x%02x };' % (name, ty, ty, int(values[name], 0)))
+    return '\n'.join(out) + '\n'
+
+
+def gen_names(defines):
+    out = [HEADER_NOTE]
+    out.append('//')
+    out.append('// `(code, name)` pairs. Primary names come first, in the order of the header, followed by')
+    out.append('// aliases, so that lookups by code find the primary name.')
+    for table, prefix in NAMES:
+        out.append('')
+        out.append('const %s: &[(u16, &str)] = &[' % table)
+        found = codes(defines, prefix)
+        primaries = [c for c in found if c[0] == c[4]]
+        aliases = [c for c in found if c[0] != c[4]]
+        for name, _, _, number, _ in primaries + aliases:
+            out.append('    (0x%02x, "%s"),' % (number, name))
+        out.append('];')
+    return '\n'.join(out) + '\n'
+
+
+def main():
+    defines = parse(HEADER) + [(name, value, comment) for name, value, comment, _ in NEWER]
+    with open('src/codes.rs', 'w') as f:
+        f.write(gen_codes(defines))
+    with open('src/code_names.rs', 'w') as f:
+        f.write(gen_names(defines))
+
+
+if __name__ == '__main__':
+    sys.exit(main())